@@ -1,4 +1,6 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -6,4 +8,24 @@ pub struct Args {
     /// Print the default config and exit.
     #[arg(long)]
     pub print_default_config: Option<Option<String>>,
+
+    /// Print a JSON Schema describing the config and exit.
+    #[arg(long)]
+    pub print_config_schema: bool,
+
+    /// Path to the config file. Defaults to $XDG_CONFIG_HOME/coolbar/config.{yaml,toml,json}.
+    #[arg(short, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Check a config file for problems and exit. Exits with a non-zero status if any are found.
+    Check {
+        /// Path to the config file. Defaults to the same file coolbar would load.
+        path: Option<PathBuf>,
+    },
 }
@@ -4,7 +4,7 @@ use relm4::Component;
 use relm4::{gtk, ComponentParts, ComponentSender};
 
 use crate::config::Icon;
-use crate::util;
+use crate::util::{self, UtilWidgetExt};
 
 pub struct IconButtonModel {
     icon: String,
@@ -49,6 +49,9 @@ impl Component for IconButtonModel {
             },
 
             gtk::Box {
+                // Stack the icon over the label on vertical bars
+                connect_realize => |container| container.orient_along_bar(),
+
                 gtk::Label {
                     set_css_classes: &["icon"],
                     #[watch]
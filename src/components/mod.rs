@@ -10,4 +10,14 @@ The macro will:
   - Give AppModel a vector to store multiple of each component
   - Create generate_child_from_config extension function
 */
-component_list![power, time, volume, workspaces, razer_mouse];
+component_list![
+    power,
+    time,
+    volume,
+    workspaces,
+    razer_mouse,
+    window_title,
+    taskbar,
+    submap,
+    screen_share
+];
@@ -3,10 +3,14 @@ use relm4::{
     component::{AsyncComponentParts, SimpleAsyncComponent},
     gtk, AsyncComponentSender,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
-use crate::{config::Icon, widgets::panel::Panel};
+use crate::{
+    config::{Icon, Problem, Validate},
+    widgets::panel::Panel,
+};
 
 pub struct PowerModel {
     icon: Icon,
@@ -21,7 +25,8 @@ pub enum PowerInput {
 #[derive(Debug)]
 pub enum PowerOutput {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct PowerInit {
     pub icon: Icon,
 }
@@ -36,6 +41,12 @@ impl Default for PowerInit {
     }
 }
 
+impl Validate for PowerInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+    }
+}
+
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for PowerModel {
     type Input = PowerInput;
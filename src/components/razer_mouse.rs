@@ -3,12 +3,13 @@ use relm4::{
     gtk::{self, traits::BoxExt},
     AsyncComponentSender, Component, ComponentController, Controller,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::{
     components::iconbutton::{IconButtonInit, IconButtonModel},
-    config::Icon,
+    config::{Icon, Problem, Validate},
     reducers::openrazer::REDUCER as OPENRAZER,
     util,
 };
@@ -33,7 +34,8 @@ pub enum RazerMouseInput {
 #[derive(Debug)]
 pub enum RazerMouseOutput {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct RazerMouseInit {
     pub icon: Icon,
     pub icon_charging: Icon,
@@ -53,6 +55,14 @@ impl Default for RazerMouseInit {
     }
 }
 
+impl Validate for RazerMouseInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        self.icon_charging
+            .validate(&format!("{key}.icon_charging"), problems);
+    }
+}
+
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for RazerMouseModel {
     type Input = RazerMouseInput;
@@ -81,7 +91,9 @@ impl SimpleAsyncComponent for RazerMouseModel {
         });
         relm4::spawn(async move {
             while let Some(msg) = rx.recv().await {
-                sender.input(msg);
+                if sender.input_sender().send(msg).is_err() {
+                    break;
+                }
             }
         });
 
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use relm4::{
+    component::{AsyncComponentParts, SimpleAsyncComponent},
+    gtk::{self, traits::WidgetExt},
+    AsyncComponentSender,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::debug;
+
+use crate::{
+    config::{Icon, Problem, Validate},
+    data::wayland_compositor::{ScreencastOwner, WaylandCompositor},
+    reducers::{
+        self,
+        pipewire::{CaptureKind, PipeWireReducer, REDUCER as PIPEWIRE},
+    },
+    util::UtilWidgetExt,
+};
+
+pub struct ScreenShareModel {
+    init: ScreenShareInit,
+    /// What the compositor is sharing, if anything.
+    screencast: Option<ScreencastOwner>,
+    /// Applications capturing audio and video.
+    microphone: Vec<String>,
+    camera: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum ScreenShareInput {
+    Compositor(Arc<dyn WaylandCompositor>),
+    PipeWire(PipeWireReducer),
+}
+
+#[derive(Debug)]
+pub enum ScreenShareOutput {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ScreenShareInit {
+    pub icon: Icon,
+    /// Also watch PipeWire for applications capturing audio or video. Requires `pw-dump`.
+    pub pipewire: bool,
+    pub microphone_icon: Icon,
+    pub camera_icon: Icon,
+}
+
+impl Default for ScreenShareInit {
+    fn default() -> Self {
+        Self {
+            icon: Icon::Material {
+                id: "screen_share".into(),
+            },
+            pipewire: false,
+            microphone_icon: Icon::Material { id: "mic".into() },
+            camera_icon: Icon::Material {
+                id: "videocam".into(),
+            },
+        }
+    }
+}
+
+impl Validate for ScreenShareInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        self.microphone_icon
+            .validate(&format!("{key}.microphone_icon"), problems);
+        self.camera_icon
+            .validate(&format!("{key}.camera_icon"), problems);
+    }
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for ScreenShareModel {
+    type Input = ScreenShareInput;
+    type Output = ScreenShareOutput;
+    type Init = ScreenShareInit;
+
+    view! {
+        #[root]
+        gtk::Box {
+            set_css_classes: &["screen_share"],
+            #[watch]
+            set_visible: model.is_live(),
+            #[watch]
+            set_tooltip_text: Some(&model.tooltip()),
+            connect_realize => |container| container.orient_along_bar(),
+
+            gtk::Label {
+                set_css_classes: &["icon"],
+                set_markup: &model.init.icon.to_string(),
+                #[watch]
+                set_visible: model.screencast.is_some(),
+            },
+            gtk::Label {
+                set_css_classes: &["icon"],
+                set_markup: &model.init.microphone_icon.to_string(),
+                #[watch]
+                set_visible: !model.microphone.is_empty(),
+            },
+            gtk::Label {
+                set_css_classes: &["icon"],
+                set_markup: &model.init.camera_icon.to_string(),
+                #[watch]
+                set_visible: !model.camera.is_empty(),
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        debug!("initializing screen share component");
+
+        let (tx, rx) = relm4::channel::<ScreenShareInput>();
+        reducers::subscribe_to_compositor(&tx, ScreenShareInput::Compositor);
+        // Only subscribing starts pw-dump
+        if init.pipewire {
+            PIPEWIRE.subscribe(&tx, |data| ScreenShareInput::PipeWire(data.clone()));
+        }
+        task::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if sender.input_sender().send(data).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let model = ScreenShareModel {
+            init,
+            screencast: None,
+            microphone: Vec::new(),
+            camera: Vec::new(),
+        };
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+        match message {
+            ScreenShareInput::Compositor(compositor) => {
+                let screencast = compositor.screencast();
+                self.screencast = screencast.active.then_some(screencast.owner);
+            }
+            ScreenShareInput::PipeWire(pipewire) => {
+                let applications = |kind| {
+                    let mut applications: Vec<String> = pipewire
+                        .capturing(kind)
+                        .map(|stream| stream.application.clone())
+                        .collect();
+                    applications.sort();
+                    applications.dedup();
+                    applications
+                };
+                self.microphone = applications(CaptureKind::Audio);
+                self.camera = applications(CaptureKind::Video);
+            }
+        }
+    }
+}
+
+impl ScreenShareModel {
+    fn is_live(&self) -> bool {
+        self.screencast.is_some() || !self.microphone.is_empty() || !self.camera.is_empty()
+    }
+
+    fn tooltip(&self) -> String {
+        let mut lines = Vec::new();
+        match self.screencast {
+            Some(ScreencastOwner::Monitor) => lines.push("Sharing a monitor".to_owned()),
+            Some(ScreencastOwner::Window) => lines.push("Sharing a window".to_owned()),
+            None => {}
+        }
+        if !self.microphone.is_empty() {
+            lines.push(format!("Recording audio: {}", self.microphone.join(", ")));
+        }
+        if !self.camera.is_empty() {
+            lines.push(format!("Recording video: {}", self.camera.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use relm4::{
+    component::{AsyncComponentParts, SimpleAsyncComponent},
+    gtk::{self, glib, pango, traits::WidgetExt},
+    AsyncComponentSender,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::debug;
+
+use crate::{
+    config::{Icon, Problem, Validate},
+    data::wayland_compositor::WaylandCompositor,
+    pango_span, reducers,
+    util::UtilWidgetExt,
+};
+
+pub struct SubmapModel {
+    init: SubmapInit,
+    visible: bool,
+    icon: String,
+    text: String,
+}
+
+#[derive(Debug)]
+pub enum SubmapInput {
+    Update(Arc<dyn WaylandCompositor>),
+}
+
+#[derive(Debug)]
+pub enum SubmapOutput {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SubmapInit {
+    pub icon: Icon,
+    /// Text colour, e.g. `#f5a97f` or `orange`. Uses the stylesheet's colour when omitted.
+    pub color: Option<String>,
+    /// Overrides for submaps by name.
+    pub submaps: BTreeMap<String, SubmapStyle>,
+}
+
+impl Default for SubmapInit {
+    fn default() -> Self {
+        Self {
+            icon: Icon::Material {
+                id: "keyboard".into(),
+            },
+            color: None,
+            submaps: BTreeMap::new(),
+        }
+    }
+}
+
+impl Validate for SubmapInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        validate_color(self.color.as_deref(), &format!("{key}.color"), problems);
+        for (name, style) in &self.submaps {
+            let key = format!("{key}.submaps.{name}");
+            if let Some(icon) = &style.icon {
+                icon.validate(&format!("{key}.icon"), problems);
+            }
+            validate_color(style.color.as_deref(), &format!("{key}.color"), problems);
+        }
+    }
+}
+
+fn validate_color(color: Option<&str>, key: &str, problems: &mut Vec<Problem>) {
+    if let Some(color) = color {
+        if pango::Color::parse(color).is_err() {
+            problems.push(Problem {
+                key: key.to_owned(),
+                message: format!("invalid colour \"{color}\""),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SubmapStyle {
+    pub icon: Option<Icon>,
+    pub color: Option<String>,
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for SubmapModel {
+    type Input = SubmapInput;
+    type Output = SubmapOutput;
+    type Init = SubmapInit;
+
+    view! {
+        #[root]
+        gtk::Box {
+            set_css_classes: &["submap", "iconbutton"],
+            #[watch]
+            set_visible: model.visible,
+            // Stack the icon over the label on vertical bars
+            connect_realize => |container| container.orient_along_bar(),
+
+            gtk::Label {
+                set_css_classes: &["icon"],
+                #[watch]
+                set_markup: &model.icon,
+            },
+            gtk::Label {
+                set_css_classes: &["label"],
+                #[watch]
+                set_markup: &model.text,
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        debug!("initializing submap component");
+
+        let (tx, rx) = relm4::channel::<SubmapInput>();
+        reducers::subscribe_to_compositor(&tx, SubmapInput::Update);
+        task::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if sender.input_sender().send(data).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let model = SubmapModel {
+            init,
+            visible: false,
+            icon: String::new(),
+            text: String::new(),
+        };
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+        match message {
+            SubmapInput::Update(compositor) => {
+                let Some(name) = compositor.submap() else {
+                    self.visible = false;
+                    return;
+                };
+
+                let style = self.init.submaps.get(name);
+                let icon = style
+                    .and_then(|s| s.icon.as_ref())
+                    .unwrap_or(&self.init.icon)
+                    .to_string();
+                let text = glib::markup_escape_text(name).to_string();
+                let color = style
+                    .and_then(|s| s.color.as_ref())
+                    .or(self.init.color.as_ref());
+
+                self.visible = true;
+                (self.icon, self.text) = match color {
+                    Some(color) => (
+                        pango_span!(icon, { foreground: color }),
+                        pango_span!(text, { foreground: color }),
+                    ),
+                    None => (icon, text),
+                };
+            }
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+
+use relm4::{
+    component::{AsyncComponentParts, SimpleAsyncComponent},
+    gtk::{
+        self, gdk,
+        pango::EllipsizeMode,
+        traits::{BoxExt, ButtonExt, GestureExt, GestureSingleExt, WidgetExt},
+    },
+    AsyncComponentSender,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::debug;
+
+use crate::{
+    config::{Problem, Validate},
+    data::wayland_compositor::{Action, WaylandCompositor, Window, WindowId},
+    icons, reducers,
+    util::UtilWidgetExt,
+};
+
+pub struct TaskbarModel {
+    /// Connector of the monitor on which this component exists.
+    monitor_connector: String,
+    init: TaskbarInit,
+    compositor: Option<Arc<dyn WaylandCompositor>>,
+    buttons: gtk::Box,
+}
+
+#[derive(Debug)]
+pub enum TaskbarInput {
+    Update(Arc<dyn WaylandCompositor>),
+    /// A button for the windows was clicked.
+    Click {
+        window_ids: Vec<WindowId>,
+        button: u32,
+    },
+}
+
+#[derive(Debug)]
+pub enum TaskbarOutput {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TaskbarInit {
+    pub scope: TaskbarScope,
+    /// Show one button for all windows of an application. Clicking it cycles through them.
+    pub group_by_class: bool,
+    /// Show window titles next to their icons.
+    pub titles: bool,
+    /// Maximum width of each title in characters.
+    pub max_width_chars: i32,
+}
+
+impl Default for TaskbarInit {
+    fn default() -> Self {
+        Self {
+            scope: TaskbarScope::Workspace,
+            group_by_class: false,
+            titles: false,
+            max_width_chars: 20,
+        }
+    }
+}
+
+impl Validate for TaskbarInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        if self.max_width_chars <= 0 {
+            problems.push(Problem {
+                key: format!("{key}.max_width_chars"),
+                message: "must be greater than 0".into(),
+            });
+        }
+    }
+}
+
+/// Which windows are shown.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskbarScope {
+    /// Windows on the active workspace of the monitor the bar is on.
+    Workspace,
+    /// Windows on any workspace of the monitor the bar is on.
+    Monitor,
+    All,
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for TaskbarModel {
+    type Input = TaskbarInput;
+    type Output = TaskbarOutput;
+    type Init = TaskbarInit;
+
+    view! {
+        #[root]
+        gtk::Box {
+            set_css_classes: &["taskbar"],
+
+            #[local_ref]
+            buttons -> gtk::Box {
+                connect_realize => |container| container.orient_along_bar(),
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        debug!("initializing taskbar component");
+
+        let (tx, rx) = relm4::channel::<TaskbarInput>();
+        reducers::subscribe_to_compositor(&tx, TaskbarInput::Update);
+        let sender_clone = sender.clone();
+        task::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if sender_clone.input_sender().send(data).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let model = TaskbarModel {
+            monitor_connector: root.monitor_connector(),
+            init,
+            compositor: None,
+            buttons: gtk::Box::default(),
+        };
+        let buttons = &model.buttons;
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        match message {
+            TaskbarInput::Update(compositor) => {
+                self.update_buttons(compositor.as_ref(), &sender);
+                self.compositor = Some(compositor);
+            }
+            TaskbarInput::Click { window_ids, button } => {
+                let Some(compositor) = &self.compositor else {
+                    return;
+                };
+                let focused_window_id = focused_window(compositor.as_ref()).map(|w| w.id);
+                if let Some(action) = click_action(&window_ids, focused_window_id, button) {
+                    compositor.dispatch(action);
+                }
+            }
+        }
+    }
+}
+
+impl TaskbarModel {
+    /// Returns the windows to show, in order.
+    fn windows<'a>(&self, compositor: &'a dyn WaylandCompositor) -> Vec<&'a Window> {
+        let monitor = compositor.monitors().get(&self.monitor_connector);
+        match self.init.scope {
+            TaskbarScope::Workspace => monitor
+                .and_then(|m| compositor.active_workspace(m))
+                .map(|ws| compositor.windows_in_workspace(ws))
+                .unwrap_or_default(),
+            TaskbarScope::Monitor => compositor
+                .windows()
+                .values()
+                .filter(|w| {
+                    let workspace = compositor.workspaces().get(&w.workspace_id);
+                    workspace.is_some_and(|ws| ws.monitor_connector == self.monitor_connector)
+                })
+                .collect(),
+            TaskbarScope::All => compositor.windows().values().collect(),
+        }
+    }
+
+    /// Replaces the buttons with one for each window, or each class when grouping by class.
+    fn update_buttons(
+        &self,
+        compositor: &dyn WaylandCompositor,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        while let Some(child) = self.buttons.first_child() {
+            self.buttons.remove(&child);
+        }
+
+        let groups = group_windows(self.windows(compositor), self.init.group_by_class);
+        let focused_window_id = focused_window(compositor).map(|w| w.id);
+        for group in groups {
+            let window = group[0];
+            let window_ids: Vec<WindowId> = group.iter().map(|w| w.id).collect();
+
+            let content = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            content.connect_realize(|content| content.orient_along_bar());
+            content.append(&icons::app_icon_image(&self.buttons, &window.class));
+            if group.len() > 1 {
+                let count = gtk::Label::new(Some(&group.len().to_string()));
+                count.add_css_class("count");
+                content.append(&count);
+            }
+            if self.init.titles {
+                let title = gtk::Label::new(Some(&window.title));
+                title.add_css_class("label");
+                title.set_max_width_chars(self.init.max_width_chars);
+                title.set_ellipsize(EllipsizeMode::End);
+                content.append(&title);
+            }
+
+            let titles: Vec<&str> = group.iter().map(|w| w.title.as_str()).collect();
+            let button = gtk::Button::new();
+            button.set_cursor_from_name(Some("pointer"));
+            button.set_tooltip_text(Some(&titles.join("\n")));
+            button.set_child(Some(&content));
+            if window_ids.iter().any(|id| Some(*id) == focused_window_id) {
+                button.add_css_class("active");
+            }
+            if group.iter().any(|w| w.urgent) {
+                button.add_css_class("urgent");
+            }
+
+            // Click to focus or middle-click to close
+            let click = gtk::GestureClick::new();
+            click.set_button(0);
+            let sender = sender.clone();
+            click.connect_pressed(move |gesture, _, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                let button = gesture.current_button();
+                let window_ids = window_ids.clone();
+                sender.input(TaskbarInput::Click { window_ids, button });
+            });
+            button.add_controller(click);
+
+            self.buttons.append(&button);
+        }
+    }
+}
+
+/// Returns the window with keyboard focus.
+fn focused_window(compositor: &dyn WaylandCompositor) -> Option<&Window> {
+    let monitor = compositor.active_monitor()?;
+    let workspace = compositor.active_workspace(monitor)?;
+    compositor.active_window(workspace)
+}
+
+/// Splits windows into the groups shown as buttons, keeping their order. Each window is its own
+/// group unless grouping by class.
+fn group_windows(windows: Vec<&Window>, by_class: bool) -> Vec<Vec<&Window>> {
+    let mut groups: Vec<Vec<&Window>> = Vec::new();
+    for window in windows {
+        let group = groups
+            .iter_mut()
+            .find(|group| by_class && group[0].class == window.class);
+        match group {
+            Some(group) => group.push(window),
+            None => groups.push(vec![window]),
+        }
+    }
+    groups
+}
+
+/// Returns the action for clicking the button of `window_ids` with a mouse button. Clicking cycles
+/// through the windows once one of them is focused, middle-clicking closes the focused one.
+fn click_action(
+    window_ids: &[WindowId],
+    focused_window_id: Option<WindowId>,
+    button: u32,
+) -> Option<Action> {
+    let focused = window_ids
+        .iter()
+        .position(|id| Some(*id) == focused_window_id);
+    match button {
+        gdk::BUTTON_PRIMARY => {
+            let i = focused.map_or(0, |i| (i + 1) % window_ids.len());
+            Some(Action::FocusWindow(*window_ids.get(i)?))
+        }
+        gdk::BUTTON_MIDDLE => Some(Action::CloseWindow(*window_ids.get(focused.unwrap_or(0))?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: WindowId, class: &str) -> Window {
+        Window {
+            id,
+            class: class.into(),
+            title: String::new(),
+            workspace_id: 0,
+            urgent: false,
+        }
+    }
+
+    #[test]
+    fn groups_windows_by_class() {
+        let windows = [window(1, "kitty"), window(2, "firefox"), window(3, "kitty")];
+        let ids = |groups: Vec<Vec<&Window>>| -> Vec<Vec<WindowId>> {
+            groups
+                .iter()
+                .map(|group| group.iter().map(|w| w.id).collect())
+                .collect()
+        };
+
+        assert_eq!(
+            ids(group_windows(windows.iter().collect(), true)),
+            [vec![1, 3], vec![2]]
+        );
+        assert_eq!(
+            ids(group_windows(windows.iter().collect(), false)),
+            [vec![1], vec![2], vec![3]]
+        );
+        assert!(group_windows(Vec::new(), true).is_empty());
+    }
+
+    #[test]
+    fn cycles_through_windows() {
+        let ids = [1, 2, 3];
+        let click = |focused, button| click_action(&ids, focused, button);
+
+        assert_eq!(
+            click(None, gdk::BUTTON_PRIMARY),
+            Some(Action::FocusWindow(1))
+        );
+        assert_eq!(
+            click(Some(2), gdk::BUTTON_PRIMARY),
+            Some(Action::FocusWindow(3))
+        );
+        assert_eq!(
+            click(Some(3), gdk::BUTTON_PRIMARY),
+            Some(Action::FocusWindow(1))
+        );
+        // The focused window is elsewhere
+        assert_eq!(
+            click(Some(9), gdk::BUTTON_PRIMARY),
+            Some(Action::FocusWindow(1))
+        );
+        assert_eq!(
+            click(Some(2), gdk::BUTTON_MIDDLE),
+            Some(Action::CloseWindow(2))
+        );
+        assert_eq!(
+            click(None, gdk::BUTTON_MIDDLE),
+            Some(Action::CloseWindow(1))
+        );
+        assert_eq!(click(Some(2), gdk::BUTTON_SECONDARY), None);
+        assert_eq!(click_action(&[], None, gdk::BUTTON_PRIMARY), None);
+    }
+}
@@ -7,13 +7,14 @@ use relm4::{
     gtk::{self, traits::BoxExt},
     AsyncComponentSender, Component, ComponentController, Controller,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tokio::{task, time};
 use tracing::{debug, warn};
 
 use crate::{
     components::iconbutton::{IconButtonInit, IconButtonModel, IconButtonOutput},
-    config::Icon,
+    config::{Icon, Problem, Validate},
     widgets::panel::Panel,
 };
 
@@ -35,7 +36,8 @@ pub enum TimeInput {
 #[derive(Debug)]
 pub enum Output {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct TimeInit {
     pub icon: Icon,
     /// See https://docs.rs/chrono-tz/latest/chrono_tz/#modules for timezones.
@@ -56,6 +58,20 @@ impl Default for TimeInit {
     }
 }
 
+impl Validate for TimeInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                problems.push(Problem {
+                    key: format!("{key}.timezone"),
+                    message: format!("unknown timezone \"{timezone}\""),
+                });
+            }
+        }
+    }
+}
+
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for TimeModel {
     type Input = TimeInput;
@@ -96,7 +112,9 @@ impl SimpleAsyncComponent for TimeModel {
                 interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
                 loop {
                     interval.tick().await;
-                    sender.input(TimeInput::Tick);
+                    if sender.input_sender().send(TimeInput::Tick).is_err() {
+                        break;
+                    }
                 }
             });
         }
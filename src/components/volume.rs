@@ -3,12 +3,13 @@ use relm4::{
     gtk::{self, traits::BoxExt},
     AsyncComponentSender, Component, ComponentController, Controller,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::{
     components::iconbutton::{IconButtonInit, IconButtonInput, IconButtonModel},
-    config::Icon,
+    config::{Icon, Problem, Validate},
     reducers::pulseaudio::REDUCER as PULSEAUDIO,
     util,
 };
@@ -27,7 +28,8 @@ pub enum VolumeInput {
 #[derive(Debug)]
 pub enum VolumeOutput {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct VolumeInit {
     pub icon: Icon,
     pub icon_muted: Icon,
@@ -46,6 +48,13 @@ impl Default for VolumeInit {
     }
 }
 
+impl Validate for VolumeInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        self.icon_muted.validate(&format!("{key}.icon_muted"), problems);
+    }
+}
+
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for VolumeModel {
     type Input = VolumeInput;
@@ -68,7 +77,9 @@ impl SimpleAsyncComponent for VolumeModel {
         PULSEAUDIO.subscribe(&tx, |msg| VolumeInput::Update(msg.volume, msg.muted));
         relm4::spawn(async move {
             while let Some(msg) = rx.recv().await {
-                sender.input(msg);
+                if sender.input_sender().send(msg).is_err() {
+                    break;
+                }
             }
         });
 
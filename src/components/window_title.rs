@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use regex::Regex;
+use relm4::{
+    component::{AsyncComponentParts, SimpleAsyncComponent},
+    gtk::{self, glib, pango::EllipsizeMode, traits::WidgetExt},
+    AsyncComponentSender,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::debug;
+
+use crate::{
+    config::{Icon, Problem, Validate},
+    data::wayland_compositor::WaylandCompositor,
+    reducers, util,
+    util::UtilWidgetExt,
+};
+
+pub struct WindowTitleModel {
+    /// Connector of the monitor on which this component exists.
+    monitor_connector: String,
+    init: WindowTitleInit,
+    class_icons: Vec<(Regex, Icon)>,
+    title_rewrites: Vec<(Regex, String)>,
+
+    visible: bool,
+    icon: String,
+    text: String,
+    dim: bool,
+}
+
+#[derive(Debug)]
+pub enum WindowTitleInput {
+    Update(Arc<dyn WaylandCompositor>),
+}
+
+#[derive(Debug)]
+pub enum WindowTitleOutput {}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WindowTitleInit {
+    /// Shown when no class icon matches.
+    pub icon: Icon,
+    /// `{title}` and `{class}` are replaced with the window's title and class.
+    pub format: String,
+    /// Maximum width of the text in characters.
+    pub max_width_chars: i32,
+    pub ellipsize: Ellipsize,
+    /// The icon of the first rule whose regex matches the window's class is shown.
+    pub class_icons: Vec<ClassIcon>,
+    /// Applied to the title in order before it is formatted.
+    pub title_rewrites: Vec<TitleRewrite>,
+}
+
+impl Default for WindowTitleInit {
+    fn default() -> Self {
+        Self {
+            icon: Icon::Material {
+                id: "web_asset".into(),
+            },
+            format: "{title}".into(),
+            max_width_chars: 50,
+            ellipsize: Ellipsize::End,
+            class_icons: Vec::new(),
+            title_rewrites: Vec::new(),
+        }
+    }
+}
+
+impl Validate for WindowTitleInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        if self.max_width_chars <= 0 {
+            problems.push(Problem {
+                key: format!("{key}.max_width_chars"),
+                message: "must be greater than 0".into(),
+            });
+        }
+        for (i, rule) in self.class_icons.iter().enumerate() {
+            validate_regex(
+                &rule.class,
+                &format!("{key}.class_icons[{i}].class"),
+                problems,
+            );
+            rule.icon
+                .validate(&format!("{key}.class_icons[{i}].icon"), problems);
+        }
+        for (i, rule) in self.title_rewrites.iter().enumerate() {
+            let key = format!("{key}.title_rewrites[{i}].pattern");
+            validate_regex(&rule.pattern, &key, problems);
+        }
+    }
+}
+
+fn validate_regex(regex: &str, key: &str, problems: &mut Vec<Problem>) {
+    if let Err(err) = Regex::new(regex) {
+        problems.push(Problem {
+            key: key.to_owned(),
+            message: format!("invalid regex: {err}"),
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ellipsize {
+    None,
+    Start,
+    Middle,
+    End,
+}
+
+impl From<Ellipsize> for EllipsizeMode {
+    fn from(ellipsize: Ellipsize) -> Self {
+        match ellipsize {
+            Ellipsize::None => EllipsizeMode::None,
+            Ellipsize::Start => EllipsizeMode::Start,
+            Ellipsize::Middle => EllipsizeMode::Middle,
+            Ellipsize::End => EllipsizeMode::End,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClassIcon {
+    /// Regex matched against the window's class.
+    pub class: String,
+    pub icon: Icon,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TitleRewrite {
+    /// Regex matched against the title.
+    pub pattern: String,
+    /// May refer to capture groups, e.g. `$1`.
+    pub replacement: String,
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for WindowTitleModel {
+    type Input = WindowTitleInput;
+    type Output = WindowTitleOutput;
+    type Init = WindowTitleInit;
+
+    view! {
+        #[root]
+        gtk::Button {
+            set_css_classes: &["window_title", "iconbutton"],
+            #[watch]
+            set_visible: model.visible,
+
+            gtk::Box {
+                // Stack the icon over the label on vertical bars
+                connect_realize => |container| container.orient_along_bar(),
+
+                gtk::Label {
+                    set_css_classes: &["icon"],
+                    #[watch]
+                    set_markup: &util::dim_if(model.icon.clone(), model.dim),
+                },
+                gtk::Label {
+                    set_css_classes: &["label"],
+                    set_max_width_chars: model.init.max_width_chars,
+                    set_ellipsize: model.init.ellipsize.into(),
+                    #[watch]
+                    set_markup: &util::dim_if(model.text.clone(), model.dim),
+                },
+            },
+        }
+    }
+
+    async fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        debug!("initializing window title component");
+
+        let (tx, rx) = relm4::channel::<WindowTitleInput>();
+        reducers::subscribe_to_compositor(&tx, WindowTitleInput::Update);
+        task::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                if sender.input_sender().send(data).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Invalid regexes are reported when the config is validated
+        let class_icons = init
+            .class_icons
+            .iter()
+            .filter_map(|rule| Some((Regex::new(&rule.class).ok()?, rule.icon.clone())))
+            .collect();
+        let title_rewrites = init
+            .title_rewrites
+            .iter()
+            .filter_map(|rule| Some((Regex::new(&rule.pattern).ok()?, rule.replacement.clone())))
+            .collect();
+
+        let model = WindowTitleModel {
+            monitor_connector: root.monitor_connector(),
+            icon: init.icon.to_string(),
+            init,
+            class_icons,
+            title_rewrites,
+            visible: false,
+            text: String::new(),
+            dim: false,
+        };
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+        match message {
+            WindowTitleInput::Update(compositor) => {
+                let monitor = compositor.monitors().get(&self.monitor_connector);
+                let window = monitor
+                    .and_then(|m| compositor.active_workspace(m))
+                    .and_then(|ws| compositor.active_window(ws));
+                let (Some(monitor), Some(window)) = (monitor, window) else {
+                    self.visible = false;
+                    return;
+                };
+
+                let icon = class_icon(&self.class_icons, &window.class).unwrap_or(&self.init.icon);
+                let title = rewrite_title(&self.title_rewrites, &window.title);
+                let text = format_title(&self.init.format, &window.class, &title);
+
+                self.visible = true;
+                self.icon = icon.to_string();
+                self.text = glib::markup_escape_text(&text).to_string();
+                // Dim the title while another monitor is focused
+                self.dim = !monitor.active;
+            }
+        }
+    }
+}
+
+/// Returns the icon of the first rule matching `class`.
+fn class_icon<'a>(rules: &'a [(Regex, Icon)], class: &str) -> Option<&'a Icon> {
+    rules
+        .iter()
+        .find(|(regex, _)| regex.is_match(class))
+        .map(|(_, icon)| icon)
+}
+
+/// Applies each rewrite to `title` in order.
+fn rewrite_title(rewrites: &[(Regex, String)], title: &str) -> String {
+    rewrites
+        .iter()
+        .fold(title.to_owned(), |title, (regex, replacement)| {
+            regex.replace_all(&title, replacement.as_str()).into_owned()
+        })
+}
+
+/// Substitutes `{class}` and `{title}` in `format`. The class is substituted first so that a title
+/// containing `{class}` is shown as is.
+fn format_title(format: &str, class: &str, title: &str) -> String {
+    format.replace("{class}", class).replace("{title}", title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules<T: Clone>(rules: &[(&str, T)]) -> Vec<(Regex, T)> {
+        rules
+            .iter()
+            .map(|(regex, value)| (Regex::new(regex).unwrap(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_first_class_icon() {
+        let icon = |text: &str| Icon::Literal { text: text.into() };
+        let rules = rules(&[
+            ("^(kitty|foot)$", icon("terminal")),
+            ("firefox", icon("browser")),
+            (".*", icon("other")),
+        ]);
+        let matching = |class| class_icon(&rules, class).map(Icon::to_string);
+
+        assert_eq!(matching("foot").as_deref(), Some("terminal"));
+        assert_eq!(matching("org.mozilla.firefox").as_deref(), Some("browser"));
+        assert_eq!(matching("footclient").as_deref(), Some("other"));
+        assert!(class_icon(&[], "foot").is_none());
+    }
+
+    #[test]
+    fn rewrites_title_in_order() {
+        let rewrites = rules(&[
+            (r"^(.*) — Mozilla Firefox$", "$1".to_owned()),
+            ("(?i)mozilla", "Firefox".to_owned()),
+            (r"\s+", " ".to_owned()),
+        ]);
+
+        assert_eq!(
+            rewrite_title(&rewrites, "Rust   docs — Mozilla Firefox"),
+            "Rust docs"
+        );
+        assert_eq!(rewrite_title(&rewrites, "About Mozilla"), "About Firefox");
+        assert_eq!(rewrite_title(&[], "~/crate"), "~/crate");
+    }
+
+    #[test]
+    fn formats_title() {
+        assert_eq!(format_title("{title}", "kitty", "~"), "~");
+        assert_eq!(format_title("{class}: {title}", "kitty", "~"), "kitty: ~");
+        assert_eq!(
+            format_title("{title}", "kitty", "echo {class}"),
+            "echo {class}"
+        );
+        assert_eq!(format_title("Window", "kitty", "~"), "Window");
+    }
+}
@@ -1,5 +1,7 @@
 use std::{
+    collections::BTreeMap,
     f64::consts::PI,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -9,10 +11,13 @@ use relm4::{
     gtk::{
         self,
         cairo::{self, LineCap, Operator},
-        traits::{DrawingAreaExt, WidgetExt},
+        gdk,
+        glib::{self, signal::Inhibit, Cast},
+        traits::{BoxExt, DrawingAreaExt, GestureExt, GestureSingleExt, OrientableExt, WidgetExt},
     },
-    AsyncComponentSender,
+    AsyncComponentSender, RelmWidgetExt,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tokio::{
     task,
@@ -21,54 +26,139 @@ use tokio::{
 use tracing::debug;
 
 use crate::{
-    config,
-    data::wayland_compositor::WaylandCompositor,
-    reducers::hyprland::{HyprlandReducer, REDUCER as HYPRLAND},
-    util::UtilWidgetExt,
+    config::{self, Icon, Problem, Validate},
+    data::wayland_compositor::{Action, WaylandCompositor, WorkspaceId},
+    icons, reducers,
+    util::{self, UtilWidgetExt},
 };
 
 const FAST_INTERPOLATION: Duration = Duration::from_millis(150);
 const SLOW_INTERPOLATION: Duration = Duration::from_millis(400);
+const URGENT_PULSE_PERIOD: Duration = Duration::from_millis(1200);
 
 pub struct WorkspacesModel {
     /// Connector of the monitor on which this component exists.
     monitor_connector: String,
+    init: WorkspacesInit,
+
+    /// Ids of the workspaces currently shown, in order.
+    slots: Vec<WorkspaceId>,
+    /// Whether any of the shown workspaces are urgent.
+    urgent: bool,
+    /// Whether the compositor's state is stale because the connection to it was lost.
+    disconnected: bool,
+    labels: gtk::Box,
+    special: gtk::Label,
 
     /// Indicates whether or not the next frame should be drawn.
     drawing: bool,
 
     last_update: Instant,
-    hyprland: Option<HyprlandReducer>,
+    compositor: Option<Arc<dyn WaylandCompositor>>,
     handler: DrawHandler,
     width: f64,
     height: f64,
     fast_interpolation: Duration,
     slow_interpolation: Duration,
-    dot_fast_x: f64,
-    dot_fast_x_start: f64,
-    dot_slow_x: f64,
-    dot_slow_x_start: f64,
+    animations: bool,
+
+    /// Whether the circles are laid out top to bottom instead of left to right.
+    vertical: bool,
+
+    /// Positions of the ends of the active workspace dot, along the direction the circles are laid
+    /// out in.
+    dot_fast: f64,
+    dot_fast_start: f64,
+    dot_slow: f64,
+    dot_slow_start: f64,
 }
 
 #[derive(Debug)]
 pub enum WorkspacesInput {
-    Update(HyprlandReducer),
+    Update(Arc<dyn WaylandCompositor>),
     Resize((i32, i32)),
     Draw,
+    /// A mouse button was pressed at a point in the drawing area.
+    Click {
+        x: f64,
+        y: f64,
+        button: u32,
+    },
+    /// A workspace label was clicked.
+    ClickWorkspace {
+        id: WorkspaceId,
+        button: u32,
+    },
+    Scroll(f64),
 }
 
 #[derive(Debug)]
 pub enum WorkspacesOutput {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WorkspacesInit {}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct WorkspacesInit {
+    pub count: WorkspaceCount,
+    /// Only show workspaces on the monitor the bar is on.
+    pub current_monitor_only: bool,
+    pub display: WorkspacesDisplay,
+    /// Icons shown instead of workspace names when using the `labels` display, keyed by workspace
+    /// name.
+    pub icons: BTreeMap<String, Icon>,
+    /// Shown while special workspaces, such as the scratchpad, have windows.
+    pub special_icon: Icon,
+}
 
 impl Default for WorkspacesInit {
     fn default() -> Self {
-        Self {}
+        Self {
+            count: WorkspaceCount::Fixed { count: 10 },
+            current_monitor_only: false,
+            display: WorkspacesDisplay::Circles,
+            icons: BTreeMap::new(),
+            special_icon: Icon::Material {
+                id: "layers".into(),
+            },
+        }
+    }
+}
+
+impl Validate for WorkspacesInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        if let WorkspaceCount::Fixed { count: 0 } = self.count {
+            problems.push(Problem {
+                key: format!("{key}.count.count"),
+                message: "must be greater than 0".into(),
+            });
+        }
+        for (name, icon) in &self.icons {
+            icon.validate(&format!("{key}.icons.{name}"), problems);
+        }
+        self.special_icon
+            .validate(&format!("{key}.special_icon"), problems);
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkspaceCount {
+    /// Always show the first `count` workspaces, whether or not they exist.
+    Fixed { count: usize },
+    /// Show the workspaces which currently exist.
+    Dynamic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspacesDisplay {
+    /// A circle for each workspace, filled when it has windows, with a dot on the active one.
+    Circles,
+    /// The name of each workspace, or its icon if one is configured.
+    Labels,
+    /// The icons of the applications on each workspace, or its label if it has no windows.
+    Windows,
+}
+
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for WorkspacesModel {
     type Input = WorkspacesInput;
@@ -80,37 +170,61 @@ impl SimpleAsyncComponent for WorkspacesModel {
         gtk::Button {
             set_cursor_from_name: Some("pointer"),
             set_css_classes: &["workspaces"],
-
-            #[local_ref]
-            area -> gtk::DrawingArea {
-                set_width_request: (config::get().theme.font_size_px * 14).into(),
-
-                connect_resize[sender] => move |_, x, y| {
-                    sender.input(WorkspacesInput::Resize((x, y)));
-                }
+            #[watch]
+            set_class_active: ("disconnected", model.disconnected),
+            #[watch]
+            set_tooltip_text: model.disconnected.then_some("Disconnected from the compositor"),
+
+            gtk::Box {
+                #[local_ref]
+                area -> gtk::DrawingArea {
+                    set_visible: circles,
+                    connect_resize[sender] => move |_, x, y| {
+                        sender.input(WorkspacesInput::Resize((x, y)));
+                    }
+                },
+
+                #[local_ref]
+                labels -> gtk::Box {
+                    set_visible: !circles,
+                    set_orientation: orientation,
+                },
+
+                #[local_ref]
+                special -> gtk::Label {
+                    set_visible: false,
+                    add_css_class: "special",
+                    set_markup: &special_icon,
+                },
             },
         }
     }
 
     async fn init(
-        _init: Self::Init,
+        init: Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
         debug!("initializing workspaces component");
 
-        // Connect to Hyprland
+        // Connect to the compositor
         let (tx, rx) = relm4::channel::<WorkspacesInput>();
-        HYPRLAND.subscribe(&tx, |data| WorkspacesInput::Update(data.clone()));
+        reducers::subscribe_to_compositor(&tx, WorkspacesInput::Update);
         let sender_clone = sender.clone();
         task::spawn(async move {
             while let Some(data) = rx.recv().await {
-                sender_clone.input(data);
+                if sender_clone.input_sender().send(data).is_err() {
+                    break;
+                }
             }
         });
 
         // Begin drawing
-        let monitor_config = config::get().monitor(&root);
+        let monitor_config = config::get().monitor(&root).clone();
+        let orientation = monitor_config.window.edge.orientation();
+        let vertical = orientation == gtk::Orientation::Vertical;
+        let circles = init.display == WorkspacesDisplay::Circles;
+        let special_icon = init.special_icon.to_string();
         let sender_clone = sender.clone();
         task::spawn(async move {
             let target_fps = monitor_config.animations.target_fps;
@@ -119,16 +233,28 @@ impl SimpleAsyncComponent for WorkspacesModel {
             interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
             loop {
-                sender_clone.input(WorkspacesInput::Draw);
+                if sender_clone
+                    .input_sender()
+                    .send(WorkspacesInput::Draw)
+                    .is_err()
+                {
+                    break;
+                }
                 interval.tick().await;
             }
         });
 
         let model = WorkspacesModel {
             monitor_connector: root.monitor_connector(),
+            init,
+            slots: Vec::new(),
+            urgent: false,
+            disconnected: false,
+            labels: gtk::Box::default(),
+            special: gtk::Label::default(),
             drawing: true,
             last_update: Instant::now(),
-            hyprland: None,
+            compositor: None,
             handler: DrawHandler::new(),
             width: 0.0,
             height: 0.0,
@@ -144,28 +270,70 @@ impl SimpleAsyncComponent for WorkspacesModel {
             } else {
                 Duration::from_secs(0)
             },
-            dot_fast_x: 0.0,
-            dot_fast_x_start: 0.0,
-            dot_slow_x: 0.0,
-            dot_slow_x_start: 0.0,
+            animations: monitor_config.animations.enable,
+            vertical,
+            dot_fast: 0.0,
+            dot_fast_start: 0.0,
+            dot_slow: 0.0,
+            dot_slow_start: 0.0,
         };
         let area = model.handler.drawing_area();
+        let labels = &model.labels;
+        let special = &model.special;
         let widgets = view_output!();
 
-        config::get().monitor(&root);
+        // Click to switch workspace, middle-click to move the focused window, or scroll to move
+        // to the next or previous workspace.
+        let click = gtk::GestureClick::new();
+        click.set_button(0);
+        let sender_clone = sender.clone();
+        click.connect_pressed(move |gesture, _, x, y| {
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            let button = gesture.current_button();
+            sender_clone.input(WorkspacesInput::Click { x, y, button });
+        });
+        area.add_controller(click);
+
+        let scroll = gtk::EventControllerScroll::new(
+            gtk::EventControllerScrollFlags::VERTICAL | gtk::EventControllerScrollFlags::DISCRETE,
+        );
+        scroll.connect_scroll(move |_, _, dy| {
+            sender.input(WorkspacesInput::Scroll(dy));
+            Inhibit(true)
+        });
+        root.add_controller(scroll);
+
         AsyncComponentParts { model, widgets }
     }
 
     async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
         match message {
             WorkspacesInput::Update(data) => {
-                self.last_update = Instant::now();
-                self.drawing = true;
-                self.hyprland = Some(data);
-                self.dot_fast_x_start = self.dot_fast_x;
-                self.dot_slow_x_start = self.dot_slow_x;
-
-                sender.input(WorkspacesInput::Draw);
+                let slots = self.visible_workspaces(data.as_ref());
+                if slots.len() != self.slots.len() {
+                    self.set_length_request(slots.len());
+                }
+                self.slots = slots;
+                self.urgent = self.slots.iter().any(|id| {
+                    let workspace = data.workspaces().get(id);
+                    workspace.is_some_and(|ws| data.workspace_is_urgent(ws))
+                });
+                self.disconnected = !data.connected();
+                self.update_special(data.as_ref());
+
+                match self.init.display {
+                    WorkspacesDisplay::Circles => {
+                        self.last_update = Instant::now();
+                        self.drawing = true;
+                        self.dot_fast_start = self.dot_fast;
+                        self.dot_slow_start = self.dot_slow;
+                        sender.input(WorkspacesInput::Draw);
+                    }
+                    WorkspacesDisplay::Labels | WorkspacesDisplay::Windows => {
+                        self.update_labels(data.as_ref(), &sender)
+                    }
+                }
+                self.compositor = Some(data);
             }
             WorkspacesInput::Resize((x, y)) => {
                 self.width = x as f64;
@@ -175,11 +343,243 @@ impl SimpleAsyncComponent for WorkspacesModel {
                 let ctx = self.handler.get_context();
                 self.draw(&ctx);
             }
+            WorkspacesInput::Click { x, y, button } => {
+                let id = self
+                    .geometry()
+                    .circle_at(x, y)
+                    .and_then(|i| self.slots.get(i));
+                if let Some(&id) = id {
+                    self.click(id, button);
+                }
+            }
+            WorkspacesInput::ClickWorkspace { id, button } => self.click(id, button),
+            WorkspacesInput::Scroll(dy) => {
+                let Some(compositor) = &self.compositor else {
+                    return;
+                };
+                let active_workspace_id = compositor
+                    .monitors()
+                    .get(&self.monitor_connector)
+                    .and_then(|m| m.active_workspace_id);
+                let i = self
+                    .slots
+                    .iter()
+                    .position(|id| Some(*id) == active_workspace_id);
+                let Some(i) = i else {
+                    return;
+                };
+
+                let target = if dy > 0.0 {
+                    i.checked_add(1)
+                } else {
+                    i.checked_sub(1)
+                };
+                if let Some(&target) = target.and_then(|i| self.slots.get(i)) {
+                    compositor.dispatch(Action::FocusWorkspace(target));
+                }
+            }
         };
     }
 }
 
+/// Placement of the circles, along the length of the area and centered across it.
+struct Geometry {
+    vertical: bool,
+    n_circles: usize,
+    thickness: f64,
+    radius: f64,
+    initial_offset: f64,
+    offset_per_workspace: f64,
+    center: f64,
+}
+
+impl Geometry {
+    /// Offset of the center of a circle along the length of the area.
+    fn offset(&self, i: usize) -> f64 {
+        self.initial_offset + (self.offset_per_workspace * (i as f64))
+    }
+
+    fn point(&self, offset: f64) -> (f64, f64) {
+        if self.vertical {
+            (self.center, offset)
+        } else {
+            (offset, self.center)
+        }
+    }
+
+    /// Returns the index of the circle nearest to a point, if the point is not beyond the ends.
+    fn circle_at(&self, x: f64, y: f64) -> Option<usize> {
+        let offset = if self.vertical { y } else { x };
+        let i = ((offset - self.initial_offset) / self.offset_per_workspace).round();
+        (0.0..self.n_circles as f64)
+            .contains(&i)
+            .then_some(i as usize)
+    }
+}
+
 impl WorkspacesModel {
+    /// Returns the ids of the workspaces to show, in order.
+    fn visible_workspaces(&self, compositor: &dyn WaylandCompositor) -> Vec<WorkspaceId> {
+        let on_other_monitor = |id: &WorkspaceId| {
+            self.init.current_monitor_only
+                && compositor
+                    .workspaces()
+                    .get(id)
+                    .is_some_and(|ws| ws.monitor_connector != self.monitor_connector)
+        };
+        match self.init.count {
+            WorkspaceCount::Fixed { count } => {
+                (0..count).filter(|id| !on_other_monitor(id)).collect()
+            }
+            WorkspaceCount::Dynamic => compositor
+                .workspaces()
+                .keys()
+                .copied()
+                .filter(|id| !on_other_monitor(id))
+                .collect(),
+        }
+    }
+
+    /// Sizes the drawing area to fit `n_circles` circles.
+    fn set_length_request(&self, n_circles: usize) {
+        let length = (config::get().theme.font_size_px as f64 * 1.4 * n_circles as f64) as i32;
+        let area = self.handler.drawing_area();
+        if self.vertical {
+            area.set_height_request(length);
+        } else {
+            area.set_width_request(length);
+        }
+    }
+
+    /// Replaces the labels with one for each visible workspace, or a pill of window icons when
+    /// displaying windows.
+    fn update_labels(
+        &self,
+        compositor: &dyn WaylandCompositor,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        while let Some(child) = self.labels.first_child() {
+            self.labels.remove(&child);
+        }
+
+        let active_workspace_id = compositor
+            .monitors()
+            .get(&self.monitor_connector)
+            .and_then(|m| m.active_workspace_id);
+
+        for &id in &self.slots {
+            let workspace = compositor.workspaces().get(&id);
+            let name = match workspace {
+                Some(ws) => ws.name.clone(),
+                None => (id + 1).to_string(),
+            };
+            let text = match self.init.icons.get(&name) {
+                Some(icon) => icon.to_string(),
+                None => glib::markup_escape_text(&name).to_string(),
+            };
+            let empty = workspace.map_or(true, |ws| compositor.workspace_is_empty(ws));
+
+            let label: gtk::Widget = match workspace {
+                Some(ws) if self.init.display == WorkspacesDisplay::Windows && !empty => {
+                    let pill = gtk::Box::new(self.labels.orientation(), 0);
+                    pill.add_css_class("windows");
+                    for window in compositor.windows_in_workspace(ws) {
+                        let image = icons::app_icon_image(&self.labels, &window.class);
+                        image.set_tooltip_text(Some(&window.title));
+                        if ws.active_window_id == Some(window.id) {
+                            image.add_css_class("active");
+                        }
+                        pill.append(&image);
+                    }
+                    pill.upcast()
+                }
+                _ => {
+                    let label = gtk::Label::new(None);
+                    label.set_markup(&util::dim_if(text, empty));
+                    label.upcast()
+                }
+            };
+            label.add_css_class("workspace");
+            if Some(id) == active_workspace_id {
+                label.add_css_class("active");
+            }
+            if workspace.is_some_and(|ws| compositor.workspace_is_urgent(ws)) {
+                label.add_css_class("urgent");
+            }
+
+            let click = gtk::GestureClick::new();
+            click.set_button(0);
+            let sender = sender.clone();
+            click.connect_pressed(move |gesture, _, _, _| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                let button = gesture.current_button();
+                sender.input(WorkspacesInput::ClickWorkspace { id, button });
+            });
+            label.add_controller(click);
+
+            self.labels.append(&label);
+        }
+    }
+
+    /// Shows the special workspace indicator while any special workspace has windows.
+    fn update_special(&self, compositor: &dyn WaylandCompositor) {
+        let special_workspaces = compositor.special_workspaces();
+        let active = compositor
+            .monitors()
+            .get(&self.monitor_connector)
+            .and_then(|m| compositor.active_special_workspace(m))
+            .is_some();
+        let urgent = special_workspaces.values().any(|ws| ws.urgent);
+        let names: Vec<&str> = special_workspaces.keys().map(String::as_str).collect();
+
+        self.special.set_visible(!special_workspaces.is_empty());
+        self.special.set_tooltip_text(Some(&names.join(", ")));
+        for (class, enabled) in [("active", active), ("urgent", urgent)] {
+            if enabled {
+                self.special.add_css_class(class);
+            } else {
+                self.special.remove_css_class(class);
+            }
+        }
+    }
+
+    /// Focuses a workspace, or moves the focused window to it when middle-clicked.
+    fn click(&self, id: WorkspaceId, button: u32) {
+        let Some(compositor) = &self.compositor else {
+            return;
+        };
+        match button {
+            gdk::BUTTON_PRIMARY => compositor.dispatch(Action::FocusWorkspace(id)),
+            gdk::BUTTON_MIDDLE => compositor.dispatch(Action::MoveWindowToWorkspace(id)),
+            _ => {}
+        }
+    }
+
+    fn geometry(&self) -> Geometry {
+        let (length, breadth) = if self.vertical {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+        let thickness = 2.0;
+        let spacing = 6.0;
+        let margin = spacing * 2.0;
+        let n_circles = self.slots.len().max(1) as f64;
+        let n_spaces = n_circles - 1.0;
+        let diameter =
+            (length - (spacing * n_spaces) - (thickness * n_circles) - (margin * 2.0)) / n_circles;
+        let radius = diameter / 2.0;
+        Geometry {
+            vertical: self.vertical,
+            n_circles: self.slots.len(),
+            thickness,
+            radius,
+            initial_offset: radius + (thickness / 2.0) + margin,
+            offset_per_workspace: spacing + thickness + diameter,
+            center: breadth / 2.0,
+        }
+    }
+
     fn clear(&self, ctx: &cairo::Context) {
         ctx.set_operator(Operator::Clear);
         ctx.set_source_rgba(0.0, 0.0, 0.0, 0.0);
@@ -190,30 +590,19 @@ impl WorkspacesModel {
         if !self.drawing {
             return;
         };
-        let Some(hyprland) = &self.hyprland else {
-            return
+        let Some(compositor) = &self.compositor else {
+            return;
         };
 
-        // Calculate placement of circles
-        let thickness = 2.0;
-        let spacing = 6.0;
-        let horizontal_margin = spacing * 2.0;
-        let n_circles = 10.0;
-        let n_spaces = n_circles - 1.0;
-        let diameter = (self.width
-            - (spacing * n_spaces)
-            - (thickness * n_circles)
-            - (horizontal_margin * 2.0))
-            / n_circles;
-        let radius = diameter / 2.0;
-        let offset_per_workspace = spacing + thickness + diameter;
-        let initial_offset = radius + (thickness / 2.0) + horizontal_margin;
-        let y = self.height / 2.0;
-        let x = |i: usize| initial_offset + (offset_per_workspace * (i as f64));
+        let geometry = self.geometry();
+        let Geometry {
+            thickness, radius, ..
+        } = geometry;
 
-        // Stop drawing if interpolation is finished
+        // Stop drawing if interpolation is finished, unless an urgent workspace is pulsing
         let time = self.last_update.elapsed();
-        self.drawing = time < self.slow_interpolation;
+        let pulsing = self.urgent && self.animations;
+        self.drawing = time < self.slow_interpolation || pulsing;
 
         // Get color from stylesheet
         let color = self.handler.drawing_area().color();
@@ -232,23 +621,40 @@ impl WorkspacesModel {
         {
             ctx.set_line_width(thickness);
 
-            for i in 0..(n_circles as usize) {
-                let empty = if let Some(ws) = hyprland.workspaces().get(&i) {
-                    hyprland.workspace_is_empty(ws)
+            for (i, id) in self.slots.iter().enumerate() {
+                let empty = if let Some(ws) = compositor.workspaces().get(id) {
+                    compositor.workspace_is_empty(ws)
                 } else {
                     true
                 };
+                let urgent = compositor
+                    .workspaces()
+                    .get(id)
+                    .is_some_and(|ws| compositor.workspace_is_urgent(ws));
                 let alpha = alpha * if empty { 0.5 } else { 1.0 };
 
                 ctx.set_source_rgba(red, green, blue, alpha);
-                ctx.arc(x(i), y, radius, 0.0, std::f64::consts::PI * 2.0);
+                let (x, y) = geometry.point(geometry.offset(i));
+                ctx.arc(x, y, radius, 0.0, std::f64::consts::PI * 2.0);
+                if urgent {
+                    // Fill urgent workspaces, fading in and out while animations are enabled
+                    let pulse = if pulsing {
+                        let phase = time.as_secs_f64() / URGENT_PULSE_PERIOD.as_secs_f64();
+                        0.5 - 0.5 * (phase * PI * 2.0).cos()
+                    } else {
+                        1.0
+                    };
+                    ctx.set_source_rgba(red, green, blue, alpha * pulse);
+                    ctx.fill_preserve().expect("couldn't fill arc");
+                    ctx.set_source_rgba(red, green, blue, alpha);
+                }
                 ctx.stroke().expect("couldn't stroke arc");
             }
         }
 
         // Active workspace
         {
-            let monitor = hyprland
+            let monitor = compositor
                 .monitors()
                 .values()
                 .find(|m| m.connector == self.monitor_connector);
@@ -256,48 +662,53 @@ impl WorkspacesModel {
             let Some(monitor) = monitor else {
                 return;
             };
-            let Some(active_workspace) = hyprland.active_workspace(monitor) else {
+            let Some(active_workspace) = compositor.active_workspace(monitor) else {
+                return;
+            };
+            let Some(i) = self.slots.iter().position(|id| *id == active_workspace.id) else {
                 return;
             };
 
-            let x_dest = x(active_workspace.id);
+            let dest = geometry.offset(i);
             let radius = radius - thickness * 1.5;
 
-            if self.dot_slow_x < x(0) {
-                // Set initial positions to prevent dot coming in from the left side when the
-                // animation first begins.
-                self.dot_fast_x = x_dest;
-                self.dot_fast_x_start = x_dest;
-                self.dot_slow_x = x_dest;
-                self.dot_slow_x_start = x_dest;
+            if self.dot_slow < geometry.offset(0) {
+                // Set initial positions to prevent dot coming in from the start of the area when
+                // the animation first begins.
+                self.dot_fast = dest;
+                self.dot_fast_start = dest;
+                self.dot_slow = dest;
+                self.dot_slow_start = dest;
             } else {
-                self.dot_fast_x = if time < self.fast_interpolation {
+                self.dot_fast = if time < self.fast_interpolation {
                     ease_out_sine(
                         time,
-                        self.dot_fast_x_start,
-                        x_dest - self.dot_fast_x_start,
+                        self.dot_fast_start,
+                        dest - self.dot_fast_start,
                         self.fast_interpolation,
                     )
                 } else {
-                    x_dest
+                    dest
                 };
-                self.dot_slow_x = if time < self.slow_interpolation {
+                self.dot_slow = if time < self.slow_interpolation {
                     ease_out_sine(
                         time,
-                        self.dot_slow_x_start,
-                        x_dest - self.dot_slow_x_start,
+                        self.dot_slow_start,
+                        dest - self.dot_slow_start,
                         self.slow_interpolation,
                     )
                 } else {
-                    x_dest
+                    dest
                 };
             }
 
             ctx.set_source_rgba(red, green, blue, alpha);
             ctx.set_line_width(radius * 2.0);
             ctx.set_line_cap(LineCap::Round);
-            ctx.move_to(self.dot_fast_x, y);
-            ctx.line_to(self.dot_slow_x, y);
+            let (x, y) = geometry.point(self.dot_fast);
+            ctx.move_to(x, y);
+            let (x, y) = geometry.point(self.dot_slow);
+            ctx.line_to(x, y);
             ctx.stroke().expect("couldn't stroke arc");
         }
     }
@@ -1,15 +1,58 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock},
+    time::{Duration, SystemTime},
+};
 
+use anyhow::{anyhow, bail, Context, Result};
 use rand::{rngs::SmallRng, Rng};
-use serde::{Deserialize, Serialize};
-use tokio::sync::OnceCell;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{info, trace};
 use wildflower::Pattern;
 
-use crate::{components::ComponentConfig, icons, util::UtilWidgetExt};
+use crate::{components::ComponentConfig, icons, util::UtilWidgetExt, APPLICATION_NAME};
+
+pub static CONFIG: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+/// Path provided via `--config`, if any. Otherwise the config file is discovered on each load.
+static CONFIG_PATH_OVERRIDE: OnceLock<Option<PathBuf>> = OnceLock::new();
 
-pub static CONFIG: OnceCell<Config> = OnceCell::const_new();
+/// How often watched files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A problem found while validating the config.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// Path to the offending key, e.g. `components.time.timezone`.
+    pub key: String,
+    pub message: String,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Checks for mistakes which deserialization doesn't catch, such as references to undefined
+/// components or unknown icons.
+pub trait Validate {
+    /// Appends any problems to `problems`. `key` is the path to `self` within the config.
+    fn validate(&self, _key: &str, _problems: &mut Vec<Problem>) {}
+}
+
+fn problem(problems: &mut Vec<Problem>, key: &str, message: impl Into<String>) {
+    problems.push(Problem {
+        key: key.to_owned(),
+        message: message.into(),
+    });
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Icon {
     Literal { text: String },
@@ -31,22 +74,45 @@ impl ToString for Icon {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Validate for Icon {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        match self {
+            Icon::Literal { .. } => {}
+            Icon::Multiple { icons } => {
+                for (i, icon) in icons.iter().enumerate() {
+                    icon.validate(&format!("{key}.icons[{i}]"), problems);
+                }
+            }
+            Icon::Material { id } => {
+                if !icons::material_design_icon_exists(id) {
+                    problem(problems, key, format!("unknown material icon \"{id}\""));
+                }
+            }
+        }
+    }
+}
+
+/// Durations are written in a human-readable format, e.g. "2s" or "1m 30s".
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum PollingRate {
     Constant {
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         interval: Duration,
     },
     VariedByRatio {
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         interval: Duration,
         variance: f64,
     },
     VariedByDuration {
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         interval: Duration,
         #[serde(with = "humantime_serde")]
+        #[schemars(with = "String")]
         variance: Duration,
     },
 }
@@ -72,24 +138,188 @@ impl PollingRate {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Validate for PollingRate {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        let interval = match self {
+            PollingRate::Constant { interval } => interval,
+            PollingRate::VariedByRatio { interval, variance } => {
+                if !(0.0..=1.0).contains(variance) {
+                    problem(
+                        problems,
+                        &format!("{key}.variance"),
+                        format!("variance must be between 0 and 1, got {variance}"),
+                    );
+                }
+                interval
+            }
+            PollingRate::VariedByDuration { interval, variance } => {
+                if variance > interval {
+                    problem(
+                        problems,
+                        &format!("{key}.variance"),
+                        "variance must not be longer than interval",
+                    );
+                }
+                interval
+            }
+        };
+        if interval.is_zero() {
+            problem(
+                problems,
+                &format!("{key}.interval"),
+                "interval must be longer than zero",
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Theme {
     pub font_family: String,
     /// Font size in px
     pub font_size_px: u16,
     pub outer_padding: String,
     pub background: String,
+    /// SCSS file compiled after the built-in stylesheet, so it can override the built-in styles and
+    /// use its variables and mixins, e.g. `$mauve` or `color-button`. Relative paths are resolved
+    /// from the directory containing the config file.
+    pub stylesheet: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            font_family: "Iosevka".into(),
+            font_size_px: 16,
+            outer_padding: "20px".into(),
+            background: "#24273A".into(), // Catppuccin Macchiato, base
+            stylesheet: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Animations {
     pub enable: bool,
     pub target_fps: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for Animations {
+    fn default() -> Self {
+        DEFAULT_MONITOR.animations
+    }
+}
+
+/// Theme fields which can be overridden per monitor. Omitted fields use the global theme.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ThemeOverride {
+    pub font_family: Option<String>,
+    /// Font size in px
+    pub font_size_px: Option<u16>,
+    pub outer_padding: Option<String>,
+    pub background: Option<String>,
+}
+
+/// Screen edge the bar is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Edge {
+    /// Bars on the left and right edges lay out their components vertically.
+    pub fn orientation(self) -> gtk::Orientation {
+        match self {
+            Edge::Top | Edge::Bottom => gtk::Orientation::Horizontal,
+            Edge::Left | Edge::Right => gtk::Orientation::Vertical,
+        }
+    }
+}
+
+/// Layer-shell layer the bar is drawn on, from the bottom-most to the top-most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusiveZone {
+    /// Reserve the size of the bar so other windows are not placed below it.
+    Auto,
+    /// Reserve no space, but stay clear of space reserved by other surfaces.
+    None,
+    /// Reserve no space and ignore space reserved by other surfaces.
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyboardMode {
+    /// Never receive keyboard input.
+    None,
+    /// Take keyboard focus whenever the compositor allows it, only on the top or overlay layer.
+    Exclusive,
+    /// Receive keyboard input when the user focuses the bar.
+    OnDemand,
+}
+
+/// Margins in px between the bar and the edges of the screen.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Margins {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+/// Placement of the bar window on the screen.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Window {
+    pub edge: Edge,
+    pub layer: Layer,
+    pub margins: Margins,
+    /// Fixed height of the bar in px (its width on the left and right edges). Fits the content
+    /// when omitted.
+    pub height: Option<i32>,
+    pub exclusive_zone: ExclusiveZone,
+    pub keyboard_mode: KeyboardMode,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        DEFAULT_MONITOR.window
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Monitor {
     pub animations: Animations,
+    pub window: Window,
+    /// Replaces areas of the global layout on this monitor.
+    pub layout: Option<LayoutOverride>,
+    /// Overrides parts of the global theme on this monitor.
+    pub theme: Option<ThemeOverride>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        DEFAULT_MONITOR
+    }
 }
 
 const DEFAULT_MONITOR: Monitor = Monitor {
@@ -97,36 +327,118 @@ const DEFAULT_MONITOR: Monitor = Monitor {
         enable: true,
         target_fps: 60.0,
     },
+    window: Window {
+        edge: Edge::Top,
+        layer: Layer::Background,
+        margins: Margins {
+            top: 0,
+            right: 0,
+            bottom: 0,
+            left: 0,
+        },
+        height: None,
+        exclusive_zone: ExclusiveZone::Auto,
+        keyboard_mode: KeyboardMode::None,
+    },
+    layout: None,
+    theme: None,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Layout {
     pub left: Vec<String>,
     pub center: Vec<String>,
     pub right: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            left: vec!["power".into(), "workspaces".into()],
+            center: vec!["time".into()],
+            right: vec!["razer_mouse".into(), "volume".into()],
+        }
+    }
+}
+
+/// Layout areas which can be overridden per monitor. Omitted areas use the global layout.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LayoutOverride {
+    pub left: Option<Vec<String>>,
+    pub center: Option<Vec<String>>,
+    pub right: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Providers {
     pub wayland: Wayland,
     pub openrazer: OpenRazer,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Compositor backends which provide workspaces, windows, etc. to components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Compositor {
+    Hyprland,
+    /// Niri, connected to via `$NIRI_SOCKET`.
+    Niri,
+    /// Sway or i3, connected to via `$SWAYSOCK` or `$I3SOCK`.
+    #[serde(alias = "i3")]
+    Sway,
+    /// Any compositor implementing the ext-workspace-v1 and wlr-foreign-toplevel-management
+    /// protocols, e.g. river or labwc.
+    ExtWorkspace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Wayland {
-    // TODO add support for another compositor, this currently does nothing
-    pub compositor: String,
+    pub compositor: Compositor,
+    /// Directory containing Hyprland's `.socket.sock` and `.socket2.sock`. By default it is found
+    /// in `$XDG_RUNTIME_DIR/hypr/$HYPRLAND_INSTANCE_SIGNATURE`, or in `/tmp/hypr` for older
+    /// versions of Hyprland.
+    pub hyprland_socket_dir: Option<PathBuf>,
+}
+
+impl Default for Wayland {
+    fn default() -> Self {
+        Self {
+            compositor: Compositor::Hyprland,
+            hyprland_socket_dir: None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct OpenRazer {
     pub polling_rate: PollingRate,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Default for OpenRazer {
+    fn default() -> Self {
+        Self {
+            polling_rate: PollingRate::VariedByRatio {
+                interval: Duration::from_secs(2),
+                variance: 0.25,
+            },
+        }
+    }
+}
+
+/// The user config is layered over the default config: omitted fields keep their default values,
+/// and entries in `monitors` and `components` are added to the default entries or merged into
+/// them. Set an entry to `false` (or `null`) to remove it instead.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Config {
     /// Per-monitor configuration indexed by the monitor's connector, e.g. "HDMI-1", "DP-1", or
     /// "eDP1" depending how your monitor is connected. Accepts wildcards.
+    #[serde(deserialize_with = "merge::monitors")]
+    #[schemars(with = "BTreeMap<String, merge::Layer<Monitor>>")]
     pub monitors: BTreeMap<String, Monitor>,
 
     pub theme: Theme,
@@ -135,72 +447,285 @@ pub struct Config {
 
     pub providers: Providers,
 
+    #[serde(deserialize_with = "merge::components")]
+    #[schemars(with = "BTreeMap<String, merge::Layer<ComponentConfig>>")]
     pub components: BTreeMap<String, ComponentConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            monitors: BTreeMap::from([("*".into(), DEFAULT_MONITOR)]),
-            theme: Theme {
-                font_family: "Iosevka".into(),
-                font_size_px: 16,
-                outer_padding: "20px".into(),
-                background: "#24273A".into(), // Catppuccin Macchiato, base
+            monitors: default_monitors(),
+            theme: Default::default(),
+            layout: Default::default(),
+            providers: Default::default(),
+            components: default_components(),
+        }
+    }
+}
+
+fn default_monitors() -> BTreeMap<String, Monitor> {
+    BTreeMap::from([("*".into(), DEFAULT_MONITOR)])
+}
+
+fn default_components() -> BTreeMap<String, ComponentConfig> {
+    BTreeMap::from([
+        (
+            "power".into(),
+            ComponentConfig::Power {
+                init: Default::default(),
             },
-            layout: Layout {
-                left: vec!["power".into(), "workspaces".into()],
-                center: vec!["time".into()],
-                right: vec!["razer_mouse".into(), "volume".into()],
+        ),
+        (
+            "workspaces".into(),
+            ComponentConfig::Workspaces {
+                init: Default::default(),
             },
-            providers: Providers {
-                wayland: Wayland {
-                    compositor: "hyprland".into(),
-                },
-                openrazer: OpenRazer {
-                    polling_rate: PollingRate::VariedByRatio {
-                        interval: Duration::from_secs(2),
-                        variance: 0.25,
-                    },
-                },
+        ),
+        (
+            "time".into(),
+            ComponentConfig::Time {
+                init: Default::default(),
             },
-            components: BTreeMap::from([
-                (
-                    "power".into(),
-                    ComponentConfig::Power {
-                        init: Default::default(),
-                    },
-                ),
-                (
-                    "workspaces".into(),
-                    ComponentConfig::Workspaces {
-                        init: Default::default(),
-                    },
-                ),
-                (
-                    "time".into(),
-                    ComponentConfig::Time {
-                        init: Default::default(),
-                    },
-                ),
-                (
-                    "razer_mouse".into(),
-                    ComponentConfig::RazerMouse {
-                        init: Default::default(),
-                    },
-                ),
+        ),
+        (
+            "razer_mouse".into(),
+            ComponentConfig::RazerMouse {
+                init: Default::default(),
+            },
+        ),
+        (
+            "volume".into(),
+            ComponentConfig::Volume {
+                init: Default::default(),
+            },
+        ),
+    ])
+}
+
+/// Deserializers which layer user-provided map entries over the default entries.
+mod merge {
+    use std::{collections::BTreeMap, fmt, marker::PhantomData};
+
+    use schemars::{
+        gen::SchemaGenerator,
+        schema::{InstanceType, Schema, SchemaObject, SubschemaValidation},
+        JsonSchema,
+    };
+    use serde::{
+        de::{self, value::MapAccessDeserializer, MapAccess, Unexpected, Visitor},
+        Deserialize, Deserializer, Serialize,
+    };
+    use serde_json::Value;
+
+    use super::{default_components, default_monitors, Monitor};
+    use crate::components::ComponentConfig;
+
+    /// A map entry which is either merged into the default entry or removes it.
+    pub enum Layer<T> {
+        Set(T),
+        Remove,
+    }
+
+    impl<T: JsonSchema> JsonSchema for Layer<T> {
+        fn is_referenceable() -> bool {
+            false
+        }
+
+        fn schema_name() -> String {
+            format!("Layer_for_{}", T::schema_name())
+        }
+
+        fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+            let remove = SchemaObject {
+                instance_type: Some(vec![InstanceType::Boolean, InstanceType::Null].into()),
+                enum_values: Some(vec![false.into(), serde_json::Value::Null]),
+                ..Default::default()
+            };
+            SchemaObject {
+                subschemas: Some(Box::new(SubschemaValidation {
+                    any_of: Some(vec![gen.subschema_for::<T>(), remove.into()]),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for Layer<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct LayerVisitor<T>(PhantomData<T>);
+
+            impl<'de, T: Deserialize<'de>> Visitor<'de> for LayerVisitor<T> {
+                type Value = Layer<T>;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a map, or false to remove the default entry")
+                }
+
+                fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                    if value {
+                        Err(E::invalid_value(Unexpected::Bool(value), &self))
+                    } else {
+                        Ok(Layer::Remove)
+                    }
+                }
+
+                fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                    Ok(Layer::Remove)
+                }
+
+                fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                    Ok(Layer::Remove)
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                    T::deserialize(MapAccessDeserializer::new(map)).map(Layer::Set)
+                }
+            }
+
+            deserializer.deserialize_any(LayerVisitor(PhantomData))
+        }
+    }
+
+    /// Merges `layer` into `base`. Maps are merged key by key, anything else is replaced. Entries
+    /// with a different `type`, e.g. a component of another kind, are replaced entirely.
+    pub(super) fn merge(base: &mut Value, layer: Value) {
+        match (base, layer) {
+            (Value::Object(base), Value::Object(layer))
+                if layer
+                    .get("type")
+                    .map_or(true, |ty| base.get("type") == Some(ty)) =>
+            {
+                for (key, value) in layer {
+                    match base.get_mut(&key) {
+                        Some(base_value) => merge(base_value, value),
+                        None => {
+                            base.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (base, layer) => *base = layer,
+        }
+    }
+
+    fn layered<'de, D, T>(
+        deserializer: D,
+        mut defaults: BTreeMap<String, T>,
+        key: &str,
+    ) -> Result<BTreeMap<String, T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Serialize + de::DeserializeOwned,
+    {
+        let layers = BTreeMap::<String, Layer<Value>>::deserialize(deserializer)?;
+        for (name, layer) in layers {
+            match layer {
+                Layer::Set(layer) => {
+                    let mut value = match defaults.get(&name) {
+                        Some(default) => {
+                            serde_json::to_value(default).map_err(de::Error::custom)?
+                        }
+                        None => Value::Null,
+                    };
+                    merge(&mut value, layer);
+                    let value = T::deserialize(value)
+                        .map_err(|err| de::Error::custom(format!("{key}.{name}: {err}")))?;
+                    defaults.insert(name, value);
+                }
+                Layer::Remove => {
+                    defaults.remove(&name);
+                }
+            };
+        }
+        Ok(defaults)
+    }
+
+    pub fn monitors<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, Monitor>, D::Error> {
+        layered(deserializer, default_monitors(), "monitors")
+    }
+
+    pub fn components<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, ComponentConfig>, D::Error> {
+        layered(deserializer, default_components(), "components")
+    }
+}
+
+impl Validate for Config {
+    fn validate(&self, _key: &str, problems: &mut Vec<Problem>) {
+        for (connector, monitor) in &self.monitors {
+            let target_fps = monitor.animations.target_fps;
+            if !(target_fps > 0.0 && target_fps.is_finite()) {
+                problem(
+                    problems,
+                    &format!("monitors.{connector}.animations.target_fps"),
+                    format!("target_fps must be a positive number, got {target_fps}"),
+                );
+            }
+
+            if let Some(height) = monitor.window.height.filter(|height| *height <= 0) {
+                problem(
+                    problems,
+                    &format!("monitors.{connector}.window.height"),
+                    format!("height must be a positive number, got {height}"),
+                );
+            }
+        }
+
+        let global_areas = [
+            ("layout".to_owned(), "left", Some(&self.layout.left)),
+            ("layout".to_owned(), "center", Some(&self.layout.center)),
+            ("layout".to_owned(), "right", Some(&self.layout.right)),
+        ];
+        let monitor_areas = self.monitors.iter().flat_map(|(connector, monitor)| {
+            let key = format!("monitors.{connector}.layout");
+            let layout = monitor.layout.as_ref();
+            [
+                (key.clone(), "left", layout.and_then(|l| l.left.as_ref())),
                 (
-                    "volume".into(),
-                    ComponentConfig::Volume {
-                        init: Default::default(),
-                    },
+                    key.clone(),
+                    "center",
+                    layout.and_then(|l| l.center.as_ref()),
                 ),
-            ]),
+                (key, "right", layout.and_then(|l| l.right.as_ref())),
+            ]
+        });
+        for (key, area, names) in global_areas.into_iter().chain(monitor_areas) {
+            for (i, name) in names.into_iter().flatten().enumerate() {
+                if !self.components.contains_key(name) {
+                    problem(
+                        problems,
+                        &format!("{key}.{area}[{i}]"),
+                        format!("component \"{name}\" is not defined in components"),
+                    );
+                }
+            }
+        }
+
+        self.providers
+            .openrazer
+            .polling_rate
+            .validate("providers.openrazer.polling_rate", problems);
+
+        for (name, component) in &self.components {
+            component.validate(&format!("components.{name}"), problems);
         }
     }
 }
 
 impl Config {
+    /// Returns every problem found in the config.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        self.validate("", &mut problems);
+        problems
+    }
+
     pub fn scss_variables(&self) -> String {
         let vars = [
             ("font_family", &self.theme.font_family),
@@ -211,41 +736,478 @@ impl Config {
         vars.map(|t| format!("${}: {};", t.0, t.1)).join("\n")
     }
 
+    /// Includes the `theme` mixin from the built-in stylesheet for each monitor which overrides the
+    /// theme, scoped to the CSS class returned by [`Config::monitor_css_class`].
+    pub fn scss_monitor_themes(&self) -> String {
+        self.monitors
+            .values()
+            .enumerate()
+            .filter_map(|(i, monitor)| {
+                let theme = monitor.theme.as_ref()?;
+                let font_size = theme.font_size_px.map(|px| format!("{px}px"));
+                let args = [
+                    ("font_family", &theme.font_family),
+                    ("font_size", &font_size),
+                    ("outer_padding", &theme.outer_padding),
+                    ("background", &theme.background),
+                ]
+                .map(|(name, value)| match value {
+                    Some(value) => format!("${name}: {value}"),
+                    None => format!("${name}: ${name}"),
+                })
+                .join(", ");
+                Some(format!(".window.monitor-{i} {{ @include theme({args}); }}"))
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Returns the CSS class for bars on the monitor with the given connector, if its entry in
+    /// `monitors` overrides the theme.
+    pub fn monitor_css_class(&self, connector: &str) -> Option<String> {
+        let key = self.monitor_key(connector)?;
+        self.monitors.get(key)?.theme.as_ref()?;
+        let index = self.monitors.keys().position(|k| k == key)?;
+        Some(format!("monitor-{index}"))
+    }
+
+    /// Returns the layout for the monitor with the given connector. Areas which its entry in
+    /// `monitors` doesn't override come from the global layout.
+    pub fn layout_for_connector(&self, connector: &str) -> Layout {
+        let Some(layout) = &self.monitor_for_connector(connector).layout else {
+            return self.layout.clone();
+        };
+        let area = |area: &Option<Vec<String>>, global: &Vec<String>| {
+            area.as_ref().unwrap_or(global).clone()
+        };
+        Layout {
+            left: area(&layout.left, &self.layout.left),
+            center: area(&layout.center, &self.layout.center),
+            right: area(&layout.right, &self.layout.right),
+        }
+    }
+
     pub fn monitor<T>(&self, widget: &T) -> &Monitor
     where
         T: gtk::glib::IsA<gtk::Widget>,
     {
-        let connector = widget.monitor_connector();
+        self.monitor_for_connector(&widget.monitor_connector())
+    }
 
-        if let Some(monitor) = self.monitors.get(&connector) {
-            monitor
-        } else {
-            let monitor = self
-                .monitors
-                .iter()
-                .find(|m| Pattern::new(m.0).matches(&connector));
-            if let Some(monitor) = monitor {
-                monitor.1
-            } else {
-                &DEFAULT_MONITOR
-            }
+    pub fn monitor_for_connector(&self, connector: &str) -> &Monitor {
+        self.monitor_key(connector)
+            .and_then(|key| self.monitors.get(key))
+            .unwrap_or(&DEFAULT_MONITOR)
+    }
+
+    /// Returns the key of the entry in `monitors` which applies to the given connector.
+    fn monitor_key(&self, connector: &str) -> Option<&str> {
+        if let Some((key, _)) = self.monitors.get_key_value(connector) {
+            return Some(key);
+        }
+
+        // Prefer the most specific pattern, otherwise the default "*" pattern would always shadow
+        // patterns which sort after it.
+        self.monitors
+            .keys()
+            .filter(|key| Pattern::new(*key).matches(connector))
+            .max_by_key(|key| key.chars().filter(|c| !matches!(c, '*' | '?')).count())
+            .map(|key| key.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Formats in the order they are searched for when discovering the user config file.
+    pub const ALL: [ConfigFormat; 3] = [ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "yaml",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => bail!(
+                "unsupported config file extension: {} (expected yaml, toml or json)",
+                path.display()
+            ),
+        }
+    }
+
+    /// Deserializes `text`, returning the line and column of the failure (both 1-based) along with
+    /// the error message if it fails.
+    fn deserialize<T: DeserializeOwned>(
+        &self,
+        text: &str,
+    ) -> Result<T, (Option<(usize, usize)>, String)> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|err| {
+                let location = err.location().map(|l| (l.line(), l.column()));
+                (location, err.to_string())
+            }),
+            ConfigFormat::Toml => toml::from_str(text).map_err(|err| {
+                let location = err.span().map(|span| line_and_column(text, span.start));
+                (location, err.message().to_owned())
+            }),
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|err| {
+                let location = Some((err.line(), err.column())).filter(|l| l.0 != 0);
+                (location, err.to_string())
+            }),
+        }
+    }
+}
+
+/// Converts a byte offset into a 1-based line and column.
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+#[derive(Debug)]
+pub struct ConfigParseError {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ConfigParseError {}
+
+/// Returns the directory containing the user's config files, i.e. `$XDG_CONFIG_HOME/coolbar`, or
+/// `~/.config/coolbar` if `XDG_CONFIG_HOME` is unset.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join(APPLICATION_NAME))
+}
+
+/// Finds the first of `config.yaml`, `config.toml` or `config.json` in the config directory.
+pub fn find_config_file() -> Option<PathBuf> {
+    let dir = config_dir()?;
+    ConfigFormat::ALL
+        .iter()
+        .map(|format| dir.join(format!("config.{}", format.extension())))
+        .find(|path| path.is_file())
+}
+
+/// Reads and deserializes a config file, using its extension to determine the format.
+pub fn parse(path: &Path) -> Result<Config> {
+    let format = ConfigFormat::from_path(path)?;
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+
+    format.deserialize(&text).map_err(|(location, message)| {
+        // Location is already provided separately, don't repeat it in the message.
+        let message = match location {
+            Some((line, column)) => message
+                .trim_end_matches(&format!(" at line {line} column {column}"))
+                .to_owned(),
+            None => message,
+        };
+        anyhow!(ConfigParseError {
+            path: path.to_owned(),
+            line: location.map(|l| l.0),
+            column: location.map(|l| l.1),
+            message,
+        })
+    })
+}
+
+/// Returns the path of the config file which will be loaded, if there is one.
+pub fn path() -> Option<PathBuf> {
+    match CONFIG_PATH_OVERRIDE.get() {
+        Some(Some(path)) => Some(path.clone()),
+        _ => find_config_file(),
+    }
+}
+
+fn read() -> Result<Config> {
+    match path() {
+        Some(path) => {
+            info!({ path = %path.display() }, "loading config file");
+            parse(&path)
+        }
+        None => {
+            info!("no config file found, using default config");
+            Ok(Config::default())
         }
     }
 }
 
-pub fn load() {
-    if CONFIG.initialized() {
+/// Loads the config from `path`, or from the config directory if no path is provided. Falls back to
+/// the default config if no config file exists.
+pub fn load(path: Option<&Path>) -> Result<()> {
+    if CONFIG_PATH_OVERRIDE.set(path.map(Path::to_owned)).is_err() {
         panic!("config was already loaded");
     }
 
-    let config = Config::default();
-    // TODO load user config file
-    CONFIG.set(config).expect("failed to store config");
+    let config = read()?;
+    *CONFIG.write().expect("config lock was poisoned") = Some(Arc::new(config));
+    Ok(())
+}
+
+/// Reloads the config from the same location it was originally loaded from. The current config is
+/// kept if the new config fails to load.
+pub fn reload() -> Result<()> {
+    let config = read()?;
+    *CONFIG.write().expect("config lock was poisoned") = Some(Arc::new(config));
+    Ok(())
+}
+
+/// Returns the resolved path of the user stylesheet, if one is configured.
+pub fn stylesheet_path() -> Option<PathBuf> {
+    let stylesheet = get().theme.stylesheet.clone()?;
+    if stylesheet.is_absolute() {
+        return Some(stylesheet);
+    }
+    let base = path()
+        .and_then(|path| path.parent().map(Path::to_owned))
+        .or_else(config_dir)?;
+    Some(base.join(stylesheet))
 }
 
-pub fn get() -> &'static Config {
-    match CONFIG.get() {
-        Some(config) => &config,
+pub fn get() -> Arc<Config> {
+    match CONFIG.read().expect("config lock was poisoned").as_ref() {
+        Some(config) => Arc::clone(config),
         None => panic!("config was not loaded"),
     }
 }
+
+/// Files which trigger a reload when they are created, modified or removed.
+fn watched_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    match path() {
+        Some(path) => files.push(path),
+        // Watch each candidate so that a newly created config file is picked up.
+        None => {
+            if let Some(dir) = config_dir() {
+                files.extend(
+                    ConfigFormat::ALL
+                        .iter()
+                        .map(|format| dir.join(format!("config.{}", format.extension()))),
+                );
+            }
+        }
+    }
+    files.extend(stylesheet_path());
+    files
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Polls the watched files, calling `on_change` whenever one of them changes.
+pub async fn watch<F: Fn()>(on_change: F) {
+    let mut files = watched_files();
+    let mut times = modification_times(&files);
+
+    let mut interval = time::interval(WATCH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+
+        let new_files = watched_files();
+        let new_times = modification_times(&new_files);
+        if new_files != files || new_times != times {
+            trace!("watched config files changed");
+            on_change();
+        }
+        files = new_files;
+        times = new_times;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse_yaml(text: &str) -> Config {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn merges_values() {
+        let mut value = json!({
+            "type": "time",
+            "format": "%H",
+            "icon": { "type": "material", "id": "schedule" },
+        });
+        merge::merge(
+            &mut value,
+            json!({ "format": "%M", "icon": { "id": "alarm" } }),
+        );
+        assert_eq!(
+            value,
+            json!({
+                "type": "time",
+                "format": "%M",
+                "icon": { "type": "material", "id": "alarm" },
+            })
+        );
+
+        // Maps of another type aren't merged
+        merge::merge(
+            &mut value,
+            json!({ "icon": { "type": "literal", "text": "T" } }),
+        );
+        assert_eq!(value["icon"], json!({ "type": "literal", "text": "T" }));
+        merge::merge(&mut value, json!({ "type": "volume" }));
+        assert_eq!(value, json!({ "type": "volume" }));
+    }
+
+    #[test]
+    fn merges_components() {
+        let config = parse_yaml(
+            "
+components:
+  time:
+    format: '%H:%M'
+    timezone: UTC
+  clock:
+    type: time
+",
+        );
+
+        let ComponentConfig::Time { init } = &config.components["time"] else {
+            panic!("time component changed type");
+        };
+        assert_eq!(init.format, "%H:%M");
+        assert_eq!(init.timezone.as_deref(), Some("UTC"));
+        assert!(matches!(&init.icon, Icon::Material { id } if id == "schedule"));
+
+        assert!(matches!(
+            config.components["clock"],
+            ComponentConfig::Time { .. }
+        ));
+        assert!(config.components.contains_key("power"));
+    }
+
+    #[test]
+    fn removes_entries() {
+        let config = parse_yaml(
+            "
+monitors:
+  '*': false
+components:
+  power: false
+  volume: null
+",
+        );
+        assert!(config.monitors.is_empty());
+        assert!(!config.components.contains_key("power"));
+        assert!(!config.components.contains_key("volume"));
+        assert!(config.components.contains_key("time"));
+    }
+
+    #[test]
+    fn merges_monitors() {
+        let config = parse_yaml(
+            "
+monitors:
+  '*':
+    window:
+      edge: left
+  DP-1:
+    animations:
+      enable: false
+",
+        );
+        let all = &config.monitors["*"];
+        assert_eq!(all.window.edge, Edge::Left);
+        assert_eq!(all.window.layer, Layer::Background);
+        assert!(all.animations.enable);
+
+        let monitor = &config.monitors["DP-1"];
+        assert!(!monitor.animations.enable);
+        assert_eq!(monitor.animations.target_fps, 60.0);
+        assert_eq!(monitor.window.edge, Edge::Top);
+    }
+
+    #[test]
+    fn overrides_nested_theme_keys() {
+        let config = parse_yaml(
+            "
+theme:
+  font_size_px: 20
+monitors:
+  DP-1:
+    theme:
+      background: '#000000'
+",
+        );
+        assert_eq!(config.theme.font_size_px, 20);
+        assert_eq!(config.theme.font_family, "Iosevka");
+        assert_eq!(config.theme.outer_padding, "20px");
+
+        let theme = config.monitors["DP-1"].theme.as_ref().unwrap();
+        assert_eq!(theme.background.as_deref(), Some("#000000"));
+        assert_eq!(theme.font_family, None);
+    }
+
+    #[test]
+    fn reports_invalid_entries() {
+        let result = serde_yaml::from_str::<Config>("components:\n  time:\n    type: nope\n");
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("components.time"), "{err}");
+    }
+
+    #[test]
+    fn falls_back_to_global_layout_areas() {
+        let config = parse_yaml(
+            r#"
+layout:
+  left: [workspaces]
+  center: [window_title]
+  right: [time]
+monitors:
+  eDP-1:
+    layout:
+      right: [battery, time]
+"#,
+        );
+
+        let layout = config.layout_for_connector("eDP-1");
+        assert_eq!(layout.left, ["workspaces"]);
+        assert_eq!(layout.center, ["window_title"]);
+        assert_eq!(layout.right, ["battery", "time"]);
+        assert_eq!(config.layout_for_connector("DP-1").right, ["time"]);
+    }
+}
@@ -1,37 +1,158 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Debug};
+
+use tracing::warn;
 
 pub type MonitorConnector = String;
 pub type WorkspaceId = usize;
 pub type WindowId = usize;
 
-pub trait WaylandCompositorMonitor {
-    fn connector(&self) -> &str;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    pub connector: MonitorConnector,
+    pub active: bool,
+    pub active_workspace_id: Option<WorkspaceId>,
+    /// Name of the special workspace shown over the active workspace, if any.
+    pub active_special_workspace: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub id: WorkspaceId,
+    pub name: String,
+    pub monitor_connector: MonitorConnector,
+    pub active_window_id: Option<WindowId>,
 }
 
-pub trait WaylandCompositorWorkspace {
-    fn name(&self) -> &str;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub id: WindowId,
+    pub class: String,
+    pub title: String,
+    pub workspace_id: WorkspaceId,
+    /// Whether the window is demanding attention.
+    pub urgent: bool,
 }
 
-pub trait WaylandCompositorWindow {
-    fn class(&self) -> &str;
-    fn title(&self) -> &str;
+/// A workspace outside of the numbered ones, such as a Hyprland special workspace or the Sway
+/// scratchpad. Its windows are not included in `WaylandCompositor::windows`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecialWorkspace {
+    pub name: String,
+    pub n_windows: usize,
+    /// Whether any of its windows are demanding attention.
+    pub urgent: bool,
 }
 
-pub trait WaylandCompositor {
-    type Monitor;
-    type Workspace;
-    type Window;
+/// What a screencast is sharing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScreencastOwner {
+    #[default]
+    Monitor,
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Screencast {
+    /// Whether the screen is being shared.
+    pub active: bool,
+    /// What is being shared, or what was last shared while inactive.
+    pub owner: ScreencastOwner,
+}
+
+static NO_SPECIAL_WORKSPACES: BTreeMap<String, SpecialWorkspace> = BTreeMap::new();
+
+/// Actions components can ask the compositor to perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    FocusWorkspace(WorkspaceId),
+    /// Moves the focused window to a workspace without following it.
+    MoveWindowToWorkspace(WorkspaceId),
+    FocusWindow(WindowId),
+    /// Asks the window to close, like clicking its close button.
+    CloseWindow(WindowId),
+}
+
+/// State of a Wayland compositor. Each compositor backend implements this so components can be used
+/// with any of them, see `reducers::subscribe_to_compositor`.
+pub trait WaylandCompositor: Debug + Send + Sync {
+    fn monitors(&self) -> &BTreeMap<MonitorConnector, Monitor>;
+    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Workspace>;
+    fn windows(&self) -> &BTreeMap<WindowId, Window>;
+
+    /// Special workspaces by name, for compositors which have them.
+    fn special_workspaces(&self) -> &BTreeMap<String, SpecialWorkspace> {
+        &NO_SPECIAL_WORKSPACES
+    }
+
+    /// Name of the active keybind submap, or mode, while it isn't the default one.
+    fn submap(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the compositor's state is being received. While disconnected, the last known state
+    /// is kept.
+    fn connected(&self) -> bool {
+        true
+    }
+
+    /// State of screen sharing, for compositors which report it.
+    fn screencast(&self) -> Screencast {
+        Screencast::default()
+    }
+
+    /// Asks the compositor to perform an action. The request is sent in the background and failures
+    /// are logged.
+    fn dispatch(&self, action: Action) {
+        warn!(?action, "action is not supported by this compositor");
+    }
+
+    fn active_monitor(&self) -> Option<&Monitor> {
+        self.monitors().values().find(|m| m.active)
+    }
+
+    fn monitor_is_empty(&self, monitor: &Monitor) -> bool {
+        self.workspaces_in_monitor(monitor)
+            .into_iter()
+            .all(|ws| self.workspace_is_empty(ws))
+    }
+
+    fn active_workspace(&self, monitor: &Monitor) -> Option<&Workspace> {
+        self.workspaces().get(&monitor.active_workspace_id?)
+    }
+
+    fn workspace_is_empty(&self, workspace: &Workspace) -> bool {
+        !self
+            .windows()
+            .values()
+            .any(|w| w.workspace_id == workspace.id)
+    }
+
+    fn workspace_is_urgent(&self, workspace: &Workspace) -> bool {
+        self.windows_in_workspace(workspace)
+            .into_iter()
+            .any(|w| w.urgent)
+    }
+
+    fn active_special_workspace(&self, monitor: &Monitor) -> Option<&SpecialWorkspace> {
+        self.special_workspaces()
+            .get(monitor.active_special_workspace.as_ref()?)
+    }
 
-    fn monitors(&self) -> &BTreeMap<MonitorConnector, Self::Monitor>;
-    fn active_monitor(&self) -> &Self::Monitor;
-    fn monitor_is_empty(&self, monitor: &Self::Monitor) -> bool;
+    fn workspaces_in_monitor(&self, monitor: &Monitor) -> Vec<&Workspace> {
+        self.workspaces()
+            .values()
+            .filter(|ws| ws.monitor_connector == monitor.connector)
+            .collect()
+    }
 
-    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Self::Workspace>;
-    fn active_workspace(&self, monitor: &Self::Monitor) -> Option<&Self::Workspace>;
-    fn workspace_is_empty(&self, workspace: &Self::Workspace) -> bool;
-    fn workspaces_in_monitor(&self, monitor: &Self::Monitor) -> Vec<&Self::Workspace>;
+    fn active_window(&self, workspace: &Workspace) -> Option<&Window> {
+        self.windows().get(&workspace.active_window_id?)
+    }
 
-    fn windows(&self) -> &BTreeMap<WindowId, Self::Window>;
-    fn active_window(&self, workspace: &Self::Workspace) -> Option<&Self::Window>;
-    fn windows_in_workspace(&self, workspace: &Self::Workspace) -> Vec<&Self::Window>;
+    fn windows_in_workspace(&self, workspace: &Workspace) -> Vec<&Window> {
+        self.windows()
+            .values()
+            .filter(|w| w.workspace_id == workspace.id)
+            .collect()
+    }
 }
@@ -1,11 +1,19 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
+use gtk::{glib::IsA, traits::WidgetExt};
 use tokio::sync::OnceCell;
+use tracing::debug;
 
-use crate::pango_span;
+use crate::{config, pango_span};
 
 static MATERIAL_DESIGN_ICONS_CODEPOINTS: OnceCell<BTreeMap<String, String>> = OnceCell::const_new();
 static MATERIAL_DESIGN_ICONS_MISSING_ICON_FALLBACK: &str = "f1c0"; // help_center
+static APP_ICON_MISSING_ICON_FALLBACK: &str = "application-x-executable";
 
 pub fn load_codepoints() {
     MATERIAL_DESIGN_ICONS_CODEPOINTS
@@ -21,6 +29,13 @@ pub fn load_codepoints() {
         .expect("failed to load codepoints for material design icons");
 }
 
+pub fn material_design_icon_exists(id: &str) -> bool {
+    MATERIAL_DESIGN_ICONS_CODEPOINTS
+        .get()
+        .expect("failed to get codepoints for material design icons")
+        .contains_key(id)
+}
+
 pub fn material_design_icon(id: &str) -> String {
     let codepoint = MATERIAL_DESIGN_ICONS_CODEPOINTS
         .get()
@@ -37,3 +52,150 @@ pub fn material_design_icon(id: &str) -> String {
 
     pango_span!(icon, { font: "Material Icons" })
 }
+
+/// Icons of installed applications, keyed by lowercase desktop file name and `StartupWMClass`.
+static APP_ICONS: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Returns the icon name, or path, from the desktop entry of the application with the given window
+/// class or app id.
+pub fn app_icon(class: &str) -> Option<&'static str> {
+    APP_ICONS
+        .get_or_init(load_app_icons)
+        .get(&class.to_lowercase())
+        .map(String::as_str)
+}
+
+/// Returns an image of the icon of the application with the given window class or app id, from its
+/// desktop entry or the icon theme of the widget's display.
+pub fn app_icon_image(widget: &impl IsA<gtk::Widget>, class: &str) -> gtk::Image {
+    let theme = gtk::IconTheme::for_display(&widget.display());
+    let lowercase_class = class.to_lowercase();
+    let image = match app_icon(class) {
+        Some(icon) if Path::new(icon).is_absolute() => gtk::Image::from_file(icon),
+        Some(icon) if theme.has_icon(icon) => gtk::Image::from_icon_name(icon),
+        _ if theme.has_icon(&lowercase_class) => gtk::Image::from_icon_name(&lowercase_class),
+        _ => gtk::Image::from_icon_name(APP_ICON_MISSING_ICON_FALLBACK),
+    };
+    image.set_pixel_size(config::get().theme.font_size_px.into());
+    image.add_css_class("app");
+    image
+}
+
+/// Returns the directories containing desktop entries, most important first.
+fn application_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+fn load_app_icons() -> BTreeMap<String, String> {
+    let mut app_icons = BTreeMap::new();
+
+    // Load the least important directories first so entries in later ones are overwritten
+    for dir in application_dirs().iter().rev() {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().map_or(true, |ext| ext != "desktop") {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Some((icon, wm_class)) = parse_desktop_entry(&text) else {
+                continue;
+            };
+
+            if let Some(wm_class) = wm_class {
+                app_icons.insert(wm_class.to_lowercase(), icon.clone());
+            }
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                app_icons.insert(name.to_lowercase(), icon);
+            }
+        }
+    }
+
+    debug!("loaded {} application icons", app_icons.len());
+    app_icons
+}
+
+/// Returns the `Icon` and `StartupWMClass` keys of a desktop entry.
+fn parse_desktop_entry(text: &str) -> Option<(String, Option<String>)> {
+    let mut in_desktop_entry = false;
+    let mut icon = None;
+    let mut wm_class = None;
+
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_desktop_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_desktop_entry {
+            continue;
+        }
+        match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("Icon", value)) => icon = Some(value.to_owned()),
+            Some(("StartupWMClass", value)) => wm_class = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+
+    Some((icon?, wm_class))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_desktop_entry() {
+        let entry = "[Desktop Entry]\nName=Firefox\nIcon=firefox\nStartupWMClass=Navigator\n";
+        assert_eq!(
+            parse_desktop_entry(entry),
+            Some(("firefox".into(), Some("Navigator".into())))
+        );
+    }
+
+    #[test]
+    fn requires_icon() {
+        assert_eq!(parse_desktop_entry("[Desktop Entry]\nName=Foo\n"), None);
+    }
+
+    #[test]
+    fn ignores_other_groups() {
+        let entry = "\
+[Desktop Entry]
+Icon=code
+
+[Desktop Action new-window]
+Icon=code-new-window
+StartupWMClass=other
+";
+        assert_eq!(parse_desktop_entry(entry), Some(("code".into(), None)));
+        assert_eq!(
+            parse_desktop_entry("[Desktop Action new]\nIcon=foo\n[Desktop Entry]\nName=Foo\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn trims_whitespace() {
+        let entry = "  [Desktop Entry]  \nIcon = kitty \n StartupWMClass =kitty\n";
+        assert_eq!(
+            parse_desktop_entry(entry),
+            Some(("kitty".into(), Some("kitty".into())))
+        );
+    }
+}
@@ -17,6 +17,7 @@ This macro will:
 - Create the ComponentConfig enum
 - Give AppModel vectors to store multiple of each component
 - Create generate_child_from_config extension function
+- Implement Validate for the ComponentConfig enum
 
 # Usage
 
@@ -35,6 +36,18 @@ struct RazerMouseModel { ... }
 ```
 
 The model's `Init` will be available for use in the `components` section of the user configuration.
+It must implement `Default`, which provides the values of any omitted fields, `JsonSchema`, which
+is used by `--print-config-schema`, and `crate::config::Validate`, which is used by `coolbar check`.
+
+```rust
+// File: razer_mouse.rs
+impl Validate for RazerMouseInit {
+    fn validate(&self, key: &str, problems: &mut Vec<Problem>) {
+        self.icon.validate(&format!("{key}.icon"), problems);
+        self.icon_charging.validate(&format!("{key}.icon_charging"), problems);
+    }
+}
+```
 
 ```yaml
 # File: config.yaml
@@ -60,6 +73,7 @@ components:
 macro_rules! component_list {
     [ $( $module:ident ),* ] => [
         use paste::paste;
+        use schemars::JsonSchema;
         use serde::{Deserialize, Serialize};
         use relm4::{
             gtk::traits::BoxExt,
@@ -75,7 +89,7 @@ macro_rules! component_list {
 
             // Create an enum based on each component's Init. This is used to define the
             // configuration options on a per-component-instance basis.
-            #[derive(Debug, Clone, Serialize, Deserialize)]
+            #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
             #[serde(tag = "type", rename_all = "snake_case")]
             pub enum ComponentConfig {
                 $(
@@ -87,6 +101,16 @@ macro_rules! component_list {
                 )*
             }
 
+            impl $crate::config::Validate for ComponentConfig {
+                fn validate(&self, key: &str, problems: &mut Vec<$crate::config::Problem>) {
+                    match self {
+                        $(
+                            ComponentConfig::[<$module:camel>] { init } => init.validate(key, problems),
+                        )*
+                    }
+                }
+            }
+
             // Give AppModel a vector for each component so we have somewhere to put them after
             // they are generated from a ComponentConfig.
             #[derive(Default)]
@@ -1,11 +1,19 @@
-use std::{sync::OnceLock, time::Instant};
+use std::{cell::Cell, cell::RefCell, ffi::OsStr, path::Path, sync::OnceLock, time::Instant};
 
-use anyhow::{Error, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use clap::Parser;
 use relm4::{
-    gtk::{self, prelude::ApplicationExt, traits::WidgetExt},
-    Component, ComponentParts, ComponentSender, RelmApp,
+    gtk::{
+        self, gdk, gio,
+        prelude::{
+            ApplicationExt, ApplicationExtManual, Cast, DisplayExt, ListModelExt, MonitorExt,
+        },
+        traits::{BoxExt, GtkApplicationExt, GtkWindowExt, OrientableExt, WidgetExt},
+    },
+    Component, ComponentController, ComponentParts, ComponentSender, Controller,
 };
+use rsass::input::{FsContext, SourceFile, SourceName};
+use tokio::{runtime::Handle, task};
 use tracing::{debug, error, info, trace, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
@@ -23,34 +31,36 @@ pub mod widgets;
 
 use components::AppModel;
 
-use crate::components::ConfigWidgetExt;
+use crate::{components::ConfigWidgetExt, util::UtilWidgetExt};
 
 pub const APPLICATION_NAME: &str = "coolbar";
 pub const APPLICATION_ID: &str = "none.coolbar";
 
 #[derive(Debug)]
-pub enum AppModelInput {}
+pub enum AppModelInput {
+    /// Regenerate all components from the current config.
+    Reload,
+}
 
 #[relm4::component(pub)]
 impl Component for AppModel {
     type Input = AppModelInput;
     type Output = ();
     type CommandOutput = ();
-    type Init = ();
+    type Init = gdk::Monitor;
 
     view! {
         #[root]
         gtk::Window {
             set_css_classes: &["window"],
 
+            #[name = "bar"]
             gtk::CenterBox {
                 set_css_classes: &["bar"],
 
                 #[wrap(Some)]
                 #[name = "left"]
-                set_start_widget = &gtk::Box {
-                    set_halign: gtk::Align::Start,
-                },
+                set_start_widget = &gtk::Box {},
 
                 #[wrap(Some)]
                 #[name = "center"]
@@ -59,38 +69,69 @@ impl Component for AppModel {
 
                 #[wrap(Some)]
                 #[name = "right"]
-                set_end_widget = &gtk::Box {
-                    set_halign: gtk::Align::End,
-                }
+                set_end_widget = &gtk::Box {}
             }
         }
     }
 
     fn init(
-        _init: Self::Init,
+        monitor: Self::Init,
         root: &Self::Root,
         _sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         debug!("initializing root component");
-        initialize_window(root);
+        initialize_window(root, &monitor);
+        let connector = monitor_connector(&monitor);
+        set_monitor_css_class(root, &connector);
 
         let mut model = AppModel::default();
         let widgets = view_output!();
 
-        generate_components_from_config(&mut model, &widgets);
-
-        info!("finished initializing app");
+        set_orientation(root, &widgets, &connector);
+        generate_components_from_config(&mut model, &widgets, &connector);
 
         ComponentParts { model, widgets }
     }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        _sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            AppModelInput::Reload => {
+                let connector = root.monitor_connector();
+                configure_window(root, &connector);
+                set_monitor_css_class(root, &connector);
+
+                // Dropping the controllers shuts down the old components, the reducers they were
+                // subscribed to remain connected and are picked up by the new components.
+                for container in [&widgets.left, &widgets.center, &widgets.right] {
+                    while let Some(child) = container.first_child() {
+                        container.remove(&child);
+                    }
+                }
+                *self = AppModel::default();
+                set_orientation(root, widgets, &connector);
+                generate_components_from_config(self, widgets, &connector);
+            }
+        }
+    }
 }
 
-fn generate_components_from_config(app_model: &mut AppModel, widgets: &AppModelWidgets) {
+fn generate_components_from_config(
+    app_model: &mut AppModel,
+    widgets: &AppModelWidgets,
+    connector: &str,
+) {
     let config = config::get();
+    let layout = config.layout_for_connector(connector);
     for (area, layout, container) in [
-        ("left", &config.layout.left, &widgets.left),
-        ("center", &config.layout.center, &widgets.center),
-        ("right", &config.layout.right, &widgets.right),
+        ("left", &layout.left, &widgets.left),
+        ("center", &layout.center, &widgets.center),
+        ("right", &layout.right, &widgets.right),
     ] {
         for name in layout {
             let Some(config) = config.components.get(name) else {
@@ -104,28 +145,289 @@ fn generate_components_from_config(app_model: &mut AppModel, widgets: &AppModelW
     }
 }
 
-fn initialize_window(window: &gtk::Window) {
+/// Lays out the bar horizontally or vertically depending on the edge it is attached to. Vertical
+/// bars get the `vertical` CSS class.
+fn set_orientation(window: &gtk::Window, widgets: &AppModelWidgets, connector: &str) {
+    let orientation = config::get()
+        .monitor_for_connector(connector)
+        .window
+        .edge
+        .orientation();
+
+    widgets.bar.set_orientation(orientation);
+    for container in [&widgets.left, &widgets.center, &widgets.right] {
+        container.set_orientation(orientation);
+    }
+
+    // Keep the outer areas against the ends of the bar
+    let vertical = orientation == gtk::Orientation::Vertical;
+    for (container, align) in [
+        (&widgets.left, gtk::Align::Start),
+        (&widgets.right, gtk::Align::End),
+    ] {
+        container.set_halign(if vertical { gtk::Align::Fill } else { align });
+        container.set_valign(if vertical { align } else { gtk::Align::Fill });
+    }
+
+    if vertical {
+        window.add_css_class("vertical");
+    } else {
+        window.remove_css_class("vertical");
+    }
+}
+
+/// Gives the window the CSS class of its monitor's theme override, replacing any previous one.
+fn set_monitor_css_class(window: &gtk::Window, connector: &str) {
+    for class in window.css_classes() {
+        if class.starts_with("monitor-") {
+            window.remove_css_class(&class);
+        }
+    }
+    if let Some(class) = config::get().monitor_css_class(connector) {
+        window.add_css_class(&class);
+    }
+}
+
+fn initialize_window(window: &gtk::Window, monitor: &gdk::Monitor) {
     debug!("initializing window");
     gtk4_layer_shell::init_for_window(window);
-    gtk4_layer_shell::set_layer(window, gtk4_layer_shell::Layer::Background);
-    gtk4_layer_shell::auto_exclusive_zone_enable(window);
-
-    gtk4_layer_shell::set_anchor(window, gtk4_layer_shell::Edge::Left, true);
-    gtk4_layer_shell::set_anchor(window, gtk4_layer_shell::Edge::Right, true);
-    gtk4_layer_shell::set_anchor(window, gtk4_layer_shell::Edge::Top, true);
-    gtk4_layer_shell::set_anchor(window, gtk4_layer_shell::Edge::Bottom, false);
+    gtk4_layer_shell::set_monitor(window, monitor);
+    configure_window(window, &monitor_connector(monitor));
     debug!("window initialized");
 }
 
-fn load_styles() {
-    let scss = format!(
-        "{}\n{}",
-        config::get().scss_variables(),
-        include_str!("styles.scss")
+/// Applies the window placement from the config of the monitor with the given connector.
+fn configure_window(window: &gtk::Window, connector: &str) {
+    use gtk4_layer_shell::Edge;
+
+    let config = config::get();
+    let placement = &config.monitor_for_connector(connector).window;
+
+    gtk4_layer_shell::set_layer(
+        window,
+        match placement.layer {
+            config::Layer::Background => gtk4_layer_shell::Layer::Background,
+            config::Layer::Bottom => gtk4_layer_shell::Layer::Bottom,
+            config::Layer::Top => gtk4_layer_shell::Layer::Top,
+            config::Layer::Overlay => gtk4_layer_shell::Layer::Overlay,
+        },
+    );
+
+    // Stretch along the configured edge
+    let (edge, sides) = match placement.edge {
+        config::Edge::Top => (Edge::Top, [Edge::Left, Edge::Right]),
+        config::Edge::Bottom => (Edge::Bottom, [Edge::Left, Edge::Right]),
+        config::Edge::Left => (Edge::Left, [Edge::Top, Edge::Bottom]),
+        config::Edge::Right => (Edge::Right, [Edge::Top, Edge::Bottom]),
+    };
+    for anchor in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+        gtk4_layer_shell::set_anchor(window, anchor, anchor == edge || sides.contains(&anchor));
+    }
+
+    let margins = &placement.margins;
+    gtk4_layer_shell::set_margin(window, Edge::Top, margins.top);
+    gtk4_layer_shell::set_margin(window, Edge::Right, margins.right);
+    gtk4_layer_shell::set_margin(window, Edge::Bottom, margins.bottom);
+    gtk4_layer_shell::set_margin(window, Edge::Left, margins.left);
+
+    let height = placement.height.unwrap_or(-1);
+    match placement.edge {
+        config::Edge::Top | config::Edge::Bottom => window.set_size_request(-1, height),
+        config::Edge::Left | config::Edge::Right => window.set_size_request(height, -1),
+    }
+
+    match placement.exclusive_zone {
+        config::ExclusiveZone::Auto => gtk4_layer_shell::auto_exclusive_zone_enable(window),
+        config::ExclusiveZone::None => gtk4_layer_shell::set_exclusive_zone(window, 0),
+        config::ExclusiveZone::Ignore => gtk4_layer_shell::set_exclusive_zone(window, -1),
+    }
+
+    gtk4_layer_shell::set_keyboard_mode(
+        window,
+        match placement.keyboard_mode {
+            config::KeyboardMode::None => gtk4_layer_shell::KeyboardMode::None,
+            config::KeyboardMode::Exclusive => gtk4_layer_shell::KeyboardMode::Exclusive,
+            config::KeyboardMode::OnDemand => gtk4_layer_shell::KeyboardMode::OnDemand,
+        },
+    );
+}
+
+/// A bar window and the monitor it is displayed on.
+struct Bar {
+    monitor: gdk::Monitor,
+    controller: Controller<AppModel>,
+}
+
+thread_local! {
+    static BARS: RefCell<Vec<Bar>> = const { RefCell::new(Vec::new()) };
+}
+
+fn monitor_connector(monitor: &gdk::Monitor) -> String {
+    monitor
+        .connector()
+        .map(|c| c.to_string())
+        .unwrap_or_default()
+}
+
+/// Creates a bar for each connected monitor which doesn't have one yet, and removes the bars of
+/// disconnected monitors.
+fn sync_bars(app: &gtk::Application, monitors: &gio::ListModel) {
+    let monitors: Vec<gdk::Monitor> = (0..monitors.n_items())
+        .filter_map(|i| monitors.item(i)?.downcast().ok())
+        .collect();
+
+    BARS.with(|bars| {
+        let mut bars = bars.borrow_mut();
+
+        bars.retain(|bar| {
+            let connected = monitors.contains(&bar.monitor);
+            if !connected {
+                let connector = monitor_connector(&bar.monitor);
+                info!({ connector }, "monitor disconnected, removing bar");
+                bar.controller.widget().destroy();
+            }
+            connected
+        });
+
+        for monitor in monitors {
+            if bars.iter().any(|bar| bar.monitor == monitor) {
+                continue;
+            }
+
+            let connector = monitor_connector(&monitor);
+            info!({ connector }, "creating bar");
+            let controller = AppModel::builder().launch(monitor.clone()).detach();
+            let window = controller.widget();
+            app.add_window(window);
+            window.set_visible(true);
+            bars.push(Bar {
+                monitor,
+                controller,
+            });
+        }
+    });
+}
+
+/// Reloads the config and stylesheet, then regenerates the components of every bar.
+fn reload() {
+    info!("reloading config");
+    if let Err(err) = config::reload() {
+        error!("failed to reload config, keeping current config: {err}");
+        return;
+    }
+
+    load_styles();
+    BARS.with(|bars| {
+        for bar in bars.borrow().iter() {
+            bar.controller.emit(AppModelInput::Reload);
+        }
+    });
+    info!("finished reloading config");
+}
+
+fn activate(app: &gtk::Application) {
+    // Keep running even if every monitor is disconnected.
+    std::mem::forget(app.hold());
+
+    let monitors = gdk::Display::default()
+        .expect("failed to get default display")
+        .monitors();
+    sync_bars(app, &monitors);
+    let app_clone = app.clone();
+    monitors.connect_items_changed(move |monitors, _, _, _| sync_bars(&app_clone, monitors));
+
+    debug!("watching config for changes");
+    let (tx, rx) = relm4::channel::<()>();
+    task::spawn(async move {
+        config::watch(|| tx.send(()).expect("failed to send reload")).await;
+    });
+    relm4::spawn_local(async move {
+        while rx.recv().await.is_some() {
+            reload();
+        }
+    });
+
+    info!("finished initializing app");
+}
+
+/// Returns a handle to relm4's runtime. `RelmApp::run` would normally enter it, which the
+/// reducers rely on for `tokio::task::spawn`, but it only supports a single main window.
+fn relm4_runtime() -> Handle {
+    let (tx, rx) = std::sync::mpsc::channel();
+    relm4::spawn(async move { tx.send(Handle::current()) });
+    rx.recv().expect("failed to get relm4 runtime")
+}
+
+thread_local! {
+    /// Reused for each call to [`load_styles`] so reloading replaces the previous styles.
+    static CSS_PROVIDER: gtk::CssProvider = {
+        let provider = gtk::CssProvider::new();
+        gtk::style_context_add_provider_for_display(
+            &gtk::gdk::Display::default().expect("failed to get default display"),
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+        provider
+    };
+}
+
+/// Compiles the built-in stylesheet, followed by the user stylesheet if one is provided.
+fn compile_styles(user_stylesheet: Option<&Path>) -> Result<String> {
+    let config = config::get();
+    let mut scss = format!(
+        "{}\n{}\n{}",
+        config.scss_variables(),
+        include_str!("styles.scss"),
+        config.scss_monitor_themes()
     );
-    let css = rsass::compile_scss(scss.as_bytes(), Default::default()).expect("valid scss");
-    let css = String::from_utf8(css).unwrap();
-    relm4::set_global_css(&css);
+
+    // Import the user stylesheet rather than appending it so that errors point to the right file
+    // and its own imports are resolved relative to itself.
+    let context = match user_stylesheet {
+        Some(path) => {
+            // Sass compiles an import of a .css file to a plain CSS import, so only .scss files
+            // are imported, by their stem so that Sass resolves them through the context.
+            if path.extension() != Some(OsStr::new("scss")) {
+                bail!("stylesheet must be a .scss file: {}", path.display());
+            }
+            let (context, _) = FsContext::for_path(path)
+                .map_err(|err| anyhow!("failed to read {}: {err}", path.display()))?;
+            let stem = path
+                .file_stem()
+                .ok_or_else(|| anyhow!("invalid stylesheet path: {}", path.display()))?;
+            // Sass strings only need quotes and backslashes escaped
+            let name = stem
+                .to_string_lossy()
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            scss.push_str(&format!("\n@import \"{name}\";\n"));
+            context
+        }
+        None => FsContext::for_cwd(),
+    };
+
+    let source = SourceFile::scss_bytes(scss, SourceName::root("styles.scss"));
+    let css = context
+        .with_format(Default::default())
+        .transform(source)
+        .map_err(|err| anyhow!("{err}"))?;
+    Ok(String::from_utf8(css)?)
+}
+
+fn load_styles() {
+    let user_stylesheet = config::stylesheet_path();
+    let css = compile_styles(user_stylesheet.as_deref()).or_else(|err| {
+        if user_stylesheet.is_none() {
+            return Err(err);
+        }
+        error!("failed to compile user stylesheet, using built-in styles only:\n{err}");
+        compile_styles(None)
+    });
+
+    match css {
+        Ok(css) => CSS_PROVIDER.with(|provider| provider.load_from_data(&css)),
+        Err(err) => error!("failed to compile built-in stylesheet:\n{err}"),
+    }
 }
 
 fn init_tracing() {
@@ -149,9 +451,45 @@ fn startup(app: &gtk::Application) -> Result<()> {
     Ok(())
 }
 
+/// Parses and validates a config file, printing any problems. Returns whether the config is valid.
+fn check(path: Option<&Path>) -> bool {
+    let Some(path) = path.map(Path::to_owned).or_else(config::find_config_file) else {
+        eprintln!("no config file found");
+        return false;
+    };
+
+    let config = match config::parse(&path) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    };
+
+    icons::load_codepoints();
+    let problems = config.problems();
+    if problems.is_empty() {
+        println!("{}: ok", path.display());
+        return true;
+    }
+
+    for problem in &problems {
+        eprintln!("{}: {problem}", path.display());
+    }
+    eprintln!("found {} problem(s)", problems.len());
+    false
+}
+
 fn init() -> Result<()> {
     debug!("parsing command line arguments");
     let args = args::Args::parse();
+    if let Some(args::Command::Check { path }) = &args.command {
+        let path = path.as_deref().or(args.config.as_deref());
+        if !check(path) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     if let Some(format) = args.print_default_config {
         let format = format.or(Some("yaml".into())).unwrap();
         let default_config = match format.to_lowercase().as_str() {
@@ -162,13 +500,18 @@ fn init() -> Result<()> {
         println!("{default_config}");
         return Ok(());
     }
+    if args.print_config_schema {
+        let schema = schemars::schema_for!(config::Config);
+        println!("{}", serde_json::to_string_pretty(&schema)?);
+        return Ok(());
+    }
 
     init_tracing();
     trace!("initialized tracing");
     info!("initializing app");
 
     debug!("loading config");
-    config::load();
+    config::load(args.config.as_deref())?;
 
     debug!("loading icon codepoints");
     icons::load_codepoints();
@@ -182,9 +525,16 @@ fn init() -> Result<()> {
             handle_fatal_error(err);
         }
     });
+    let activated = Cell::new(false);
+    app.connect_activate(move |app| {
+        if !activated.replace(true) {
+            activate(app);
+        }
+    });
 
     debug!("running app");
-    RelmApp::from_app(app).run::<AppModel>(());
+    let _guard = relm4_runtime().enter();
+    app.run_with_args::<&str>(&[]);
 
     Ok(())
 }
@@ -0,0 +1,661 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Context, Result};
+use relm4::{Reducer, Reducible};
+use tokio::task;
+use tracing::{error, warn};
+use wayland_client::{
+    event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+        wl_seat::{self, WlSeat},
+    },
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+    ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+    ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use crate::data::wayland_compositor::{
+    Action, Monitor, MonitorConnector, WaylandCompositor, Window, WindowId, Workspace, WorkspaceId,
+};
+
+pub static REDUCER: Reducer<ExtWorkspaceReducer> = Reducer::new();
+
+/// Value of the activated state in `zwlr_foreign_toplevel_handle_v1.state`.
+const TOPLEVEL_STATE_ACTIVATED: u32 = 2;
+
+/// Objects are identified by their protocol id, which is unique among live objects but reused once
+/// an object is destroyed.
+type ObjectId = u32;
+
+#[derive(Debug, Default)]
+struct RawOutput {
+    /// Name of the global, used to notice when the output is removed.
+    global_name: u32,
+    connector: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct RawWorkspaceGroup {
+    outputs: Vec<ObjectId>,
+    workspaces: Vec<ObjectId>,
+}
+
+#[derive(Debug, Default)]
+struct RawWorkspace {
+    id: WorkspaceId,
+    name: String,
+    active: bool,
+    urgent: bool,
+}
+
+#[derive(Debug, Default)]
+struct RawToplevel {
+    id: WindowId,
+    title: String,
+    app_id: String,
+    outputs: Vec<ObjectId>,
+    activated: bool,
+}
+
+/// State of the Wayland connection, converted to an [`ExtWorkspaceReducer`] whenever the compositor
+/// finishes sending a batch of changes.
+#[derive(Debug, Default)]
+struct ProtocolState {
+    outputs: BTreeMap<ObjectId, RawOutput>,
+    groups: BTreeMap<ObjectId, RawWorkspaceGroup>,
+    workspaces: BTreeMap<ObjectId, RawWorkspace>,
+    toplevels: BTreeMap<ObjectId, RawToplevel>,
+    /// Ids for the next workspace and window. Unlike protocol ids these are never reused, so a
+    /// new object can't be mistaken for one that was removed.
+    next_workspace_id: WorkspaceId,
+    next_window_id: WindowId,
+    /// Handles to request changes to windows, by window id.
+    toplevel_handles: BTreeMap<WindowId, ZwlrForeignToplevelHandleV1>,
+    /// Seat that activated windows get focus on.
+    seat: Option<WlSeat>,
+    connection: Option<Connection>,
+}
+
+impl ProtocolState {
+    fn bind_output(
+        &mut self,
+        registry: &WlRegistry,
+        name: u32,
+        version: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        // Version 4 added the name event, which provides the connector
+        let output: WlOutput = registry.bind(name, version.min(4), qh, ());
+        self.outputs.insert(
+            output.id().protocol_id(),
+            RawOutput {
+                global_name: name,
+                connector: None,
+            },
+        );
+    }
+
+    /// The ext-workspace protocol does not say which workspace a window is on, and the foreign
+    /// toplevel protocol only says which outputs it is on. Windows are assumed to be on the active
+    /// workspace of their output.
+    fn postprocess(&self) -> ExtWorkspaceReducer {
+        let mut state = ExtWorkspaceReducer {
+            toplevel_handles: self.toplevel_handles.clone(),
+            seat: self.seat.clone(),
+            connection: self.connection.clone(),
+            ..Default::default()
+        };
+
+        let connector = |output: &ObjectId| self.outputs.get(output)?.connector.clone();
+
+        for group in self.groups.values() {
+            let Some(connector) = group.outputs.first().and_then(connector) else {
+                continue;
+            };
+
+            let mut active_workspace_id = None;
+            for object_id in &group.workspaces {
+                let Some(raw_workspace) = self.workspaces.get(object_id) else {
+                    continue;
+                };
+                let id = raw_workspace.id;
+                if raw_workspace.active {
+                    active_workspace_id = Some(id);
+                }
+                if raw_workspace.urgent {
+                    state.urgent_workspace_ids.insert(id);
+                }
+                state.workspaces.insert(
+                    id,
+                    Workspace {
+                        id,
+                        name: raw_workspace.name.clone(),
+                        monitor_connector: connector.clone(),
+                        active_window_id: None,
+                    },
+                );
+            }
+
+            state.monitors.insert(
+                connector.clone(),
+                Monitor {
+                    connector,
+                    active: false,
+                    active_workspace_id,
+                    active_special_workspace: None,
+                },
+            );
+        }
+
+        for toplevel in self.toplevels.values() {
+            let Some(monitor) = toplevel
+                .outputs
+                .first()
+                .and_then(connector)
+                .and_then(|connector| state.monitors.get_mut(&connector))
+            else {
+                continue;
+            };
+            let Some(workspace_id) = monitor.active_workspace_id else {
+                continue;
+            };
+
+            let id = toplevel.id;
+            if toplevel.activated {
+                monitor.active = true;
+                if let Some(workspace) = state.workspaces.get_mut(&workspace_id) {
+                    workspace.active_window_id = Some(id);
+                }
+            }
+            state.windows.insert(
+                id,
+                Window {
+                    id,
+                    class: toplevel.app_id.clone(),
+                    title: toplevel.title.clone(),
+                    workspace_id,
+                    urgent: false,
+                },
+            );
+        }
+
+        state
+    }
+
+    fn emit(&self) {
+        REDUCER.emit(ExtWorkspaceInput::Refresh(self.postprocess()));
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == WlOutput::interface().name => {
+                state.bind_output(registry, name, version, qh);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                state.outputs.retain(|_, output| output.global_name != name);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            if let Some(output) = state.outputs.get_mut(&output.id().protocol_id()) {
+                output.connector = Some(name);
+                // Outputs may be named after the workspaces on them were sent
+                state.emit();
+            }
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for ProtocolState {
+    fn event(
+        _state: &mut Self,
+        _seat: &WlSeat,
+        _event: wl_seat::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        _manager: &ExtWorkspaceManagerV1,
+        event: ext_workspace_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                state
+                    .groups
+                    .insert(workspace_group.id().protocol_id(), Default::default());
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                let id = state.next_workspace_id;
+                state.next_workspace_id += 1;
+                state.workspaces.insert(
+                    workspace.id().protocol_id(),
+                    RawWorkspace {
+                        id,
+                        ..Default::default()
+                    },
+                );
+            }
+            ext_workspace_manager_v1::Event::Done => state.emit(),
+            ext_workspace_manager_v1::Event::Finished => {
+                warn!("compositor stopped sending ext-workspace events");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(ProtocolState, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceGroupHandleV1,
+        event: ext_workspace_group_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let id = handle.id().protocol_id();
+        if let ext_workspace_group_handle_v1::Event::Removed = event {
+            state.groups.remove(&id);
+            handle.destroy();
+            return;
+        }
+
+        let Some(group) = state.groups.get_mut(&id) else {
+            return;
+        };
+        match event {
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                group.outputs.push(output.id().protocol_id());
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                group.outputs.retain(|id| *id != output.id().protocol_id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                group.workspaces.push(workspace.id().protocol_id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                group
+                    .workspaces
+                    .retain(|id| *id != workspace.id().protocol_id());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceHandleV1,
+        event: ext_workspace_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let id = handle.id().protocol_id();
+        if let ext_workspace_handle_v1::Event::Removed = event {
+            state.workspaces.remove(&id);
+            handle.destroy();
+            return;
+        }
+
+        let Some(workspace) = state.workspaces.get_mut(&id) else {
+            return;
+        };
+        match event {
+            ext_workspace_handle_v1::Event::Name { name } => {
+                workspace.name = name;
+            }
+            ext_workspace_handle_v1::Event::State {
+                state: WEnum::Value(flags),
+            } => {
+                workspace.active = flags.contains(ext_workspace_handle_v1::State::Active);
+                workspace.urgent = flags.contains(ext_workspace_handle_v1::State::Urgent);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        _manager: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            let id = state.next_window_id;
+            state.next_window_id += 1;
+            state.toplevel_handles.insert(id, toplevel.clone());
+            state.toplevels.insert(
+                toplevel.id().protocol_id(),
+                RawToplevel {
+                    id,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    event_created_child!(ProtocolState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ProtocolState {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let id = handle.id().protocol_id();
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            if let Some(toplevel) = state.toplevels.remove(&id) {
+                state.toplevel_handles.remove(&toplevel.id);
+            }
+            handle.destroy();
+            state.emit();
+            return;
+        }
+
+        let Some(toplevel) = state.toplevels.get_mut(&id) else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                toplevel.title = title;
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                toplevel.app_id = app_id;
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                toplevel.outputs.push(output.id().protocol_id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                toplevel
+                    .outputs
+                    .retain(|id| *id != output.id().protocol_id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                toplevel.activated = state
+                    .chunks_exact(4)
+                    .map(|value| u32::from_ne_bytes([value[0], value[1], value[2], value[3]]))
+                    .any(|value| value == TOPLEVEL_STATE_ACTIVATED);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => state.emit(),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExtWorkspaceReducer {
+    monitors: BTreeMap<MonitorConnector, Monitor>,
+    workspaces: BTreeMap<WorkspaceId, Workspace>,
+    windows: BTreeMap<WindowId, Window>,
+    /// The protocol reports urgency for workspaces rather than windows.
+    urgent_workspace_ids: BTreeSet<WorkspaceId>,
+    toplevel_handles: BTreeMap<WindowId, ZwlrForeignToplevelHandleV1>,
+    seat: Option<WlSeat>,
+    connection: Option<Connection>,
+}
+
+impl WaylandCompositor for ExtWorkspaceReducer {
+    fn monitors(&self) -> &BTreeMap<MonitorConnector, Monitor> {
+        &self.monitors
+    }
+
+    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Workspace> {
+        &self.workspaces
+    }
+
+    fn windows(&self) -> &BTreeMap<WindowId, Window> {
+        &self.windows
+    }
+
+    fn workspace_is_urgent(&self, workspace: &Workspace) -> bool {
+        self.urgent_workspace_ids.contains(&workspace.id)
+    }
+
+    fn dispatch(&self, action: Action) {
+        let toplevel = |id| self.toplevel_handles.get(&id);
+        match action {
+            Action::FocusWindow(id) => {
+                let (Some(toplevel), Some(seat)) = (toplevel(id), &self.seat) else {
+                    warn!(id, "can't focus window");
+                    return;
+                };
+                toplevel.activate(seat);
+            }
+            Action::CloseWindow(id) => {
+                let Some(toplevel) = toplevel(id) else {
+                    warn!(id, "can't close missing window");
+                    return;
+                };
+                toplevel.close();
+            }
+            _ => {
+                warn!(?action, "action is not supported by this compositor");
+                return;
+            }
+        }
+
+        // The event queue only flushes requests when it next wakes up
+        if let Some(Err(err)) = self.connection.as_ref().map(Connection::flush) {
+            error!("failed to send window request: {err}");
+        }
+    }
+}
+
+pub enum ExtWorkspaceInput {
+    Refresh(ExtWorkspaceReducer),
+}
+
+impl Reducible for ExtWorkspaceReducer {
+    type Input = ExtWorkspaceInput;
+
+    fn init() -> Self {
+        // The Wayland event queue blocks while waiting for events
+        task::spawn_blocking(|| {
+            if let Err(err) = dispatch_events() {
+                error!("ext-workspace connection failed: {err}");
+            }
+        });
+        Self::default()
+    }
+
+    fn reduce(&mut self, input: Self::Input) -> bool {
+        match input {
+            ExtWorkspaceInput::Refresh(state) => {
+                *self = state;
+            }
+        }
+        true
+    }
+}
+
+fn dispatch_events() -> Result<()> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, mut queue) = registry_queue_init::<ProtocolState>(&conn)?;
+    let qh = queue.handle();
+    let mut state = ProtocolState {
+        connection: Some(conn.clone()),
+        ..Default::default()
+    };
+
+    globals
+        .bind::<ExtWorkspaceManagerV1, _, _>(&qh, 1..=1, ())
+        .context("compositor does not support ext-workspace-v1")?;
+    if let Err(err) = globals.bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ()) {
+        warn!("wlr-foreign-toplevel-management is unsupported, windows will be missing: {err}");
+    }
+    match globals.bind::<WlSeat, _, _>(&qh, 1..=1, ()) {
+        Ok(seat) => state.seat = Some(seat),
+        Err(err) => warn!("no seat, windows can't be focused: {err}"),
+    }
+
+    for global in globals.contents().clone_list() {
+        if global.interface == WlOutput::interface().name {
+            state.bind_output(globals.registry(), global.name, global.version, &qh);
+        }
+    }
+
+    loop {
+        queue.blocking_dispatch(&mut state)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One output with two workspaces, the first of which was announced before others that have
+    /// since been removed.
+    fn protocol_state(connector: Option<&str>) -> ProtocolState {
+        ProtocolState {
+            outputs: BTreeMap::from([(
+                1,
+                RawOutput {
+                    global_name: 40,
+                    connector: connector.map(str::to_owned),
+                },
+            )]),
+            groups: BTreeMap::from([(
+                2,
+                RawWorkspaceGroup {
+                    outputs: vec![1],
+                    workspaces: vec![10, 11],
+                },
+            )]),
+            workspaces: BTreeMap::from([
+                (
+                    10,
+                    RawWorkspace {
+                        id: 3,
+                        name: "web".into(),
+                        active: true,
+                        urgent: false,
+                    },
+                ),
+                (
+                    11,
+                    RawWorkspace {
+                        id: 0,
+                        name: "chat".into(),
+                        active: false,
+                        urgent: true,
+                    },
+                ),
+            ]),
+            toplevels: BTreeMap::from([
+                (
+                    20,
+                    RawToplevel {
+                        id: 7,
+                        title: "Mozilla Firefox".into(),
+                        app_id: "firefox".into(),
+                        outputs: vec![1],
+                        activated: true,
+                    },
+                ),
+                (
+                    21,
+                    RawToplevel {
+                        id: 8,
+                        title: "~".into(),
+                        app_id: "kitty".into(),
+                        outputs: vec![],
+                        activated: false,
+                    },
+                ),
+            ]),
+            next_workspace_id: 4,
+            next_window_id: 9,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn postprocesses_state() {
+        let state = protocol_state(Some("DP-1")).postprocess();
+
+        assert_eq!(state.workspaces().keys().collect::<Vec<_>>(), [&0, &3]);
+        assert_eq!(state.workspaces()[&3].name, "web");
+        assert!(state.workspace_is_urgent(&state.workspaces()[&0]));
+        assert!(!state.workspace_is_urgent(&state.workspaces()[&3]));
+
+        let monitor = state.active_monitor().unwrap();
+        assert_eq!(monitor.connector, "DP-1");
+        assert_eq!(monitor.active_workspace_id, Some(3));
+
+        // Windows without an output can't be placed on a workspace
+        assert_eq!(state.windows().keys().collect::<Vec<_>>(), [&7]);
+        assert_eq!(state.windows()[&7].workspace_id, 3);
+        let window = state.active_window(&state.workspaces()[&3]).unwrap();
+        assert_eq!(window.class, "firefox");
+    }
+
+    #[test]
+    fn waits_for_output_names() {
+        let state = protocol_state(None).postprocess();
+
+        assert!(state.monitors().is_empty());
+        assert!(state.workspaces().is_empty());
+        assert!(state.windows().is_empty());
+    }
+}
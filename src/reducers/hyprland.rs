@@ -1,27 +1,43 @@
 // TODO consider using hyprland crate
-use std::{collections::BTreeMap, env};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, mem,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use relm4::{Reducer, Reducible};
 use serde::Deserialize;
 use tokio::{
-    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
     net::UnixStream,
     task,
+    time::sleep,
 };
-use tracing::{error, trace};
-
-use crate::data::wayland_compositor::{
-    MonitorConnector, WaylandCompositor, WaylandCompositorMonitor, WaylandCompositorWindow,
-    WaylandCompositorWorkspace, WindowId, WorkspaceId,
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    config,
+    data::wayland_compositor::{
+        Action, Monitor, MonitorConnector, Screencast, ScreencastOwner, SpecialWorkspace,
+        WaylandCompositor, Window, WindowId, Workspace, WorkspaceId,
+    },
 };
 
 pub static REDUCER: Reducer<HyprlandReducer> = Reducer::new();
 
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Events received before the state is refreshed are kept up to this limit.
+const MAX_PENDING_EVENTS: usize = 1024;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct HyprlandWrappedWorkspaceId {
-    // Use isize because Hyprland uses ID -99 for the special workspace.
+    // Use isize because Hyprland uses negative IDs for special workspaces.
     pub id: isize,
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,36 +50,33 @@ pub struct RawHyprlandMonitor {
 
     #[serde(rename = "activeWorkspace")]
     pub active_workspace: HyprlandWrappedWorkspaceId,
+
+    /// Has ID 0 when no special workspace is shown.
+    #[serde(rename = "specialWorkspace")]
+    pub special_workspace: Option<HyprlandWrappedWorkspaceId>,
 }
 
 impl RawHyprlandMonitor {
-    fn postprocess(raw: Vec<RawHyprlandMonitor>) -> BTreeMap<MonitorConnector, HyprlandMonitor> {
+    fn postprocess(raw: Vec<RawHyprlandMonitor>) -> Result<BTreeMap<MonitorConnector, Monitor>> {
         let mut monitors = BTreeMap::new();
 
         for raw_monitor in raw {
             let connector = raw_monitor.connector;
-            let processed_monitor = HyprlandMonitor {
+            let processed_monitor = Monitor {
                 connector: connector.clone(),
                 active: raw_monitor.active,
-                active_workspace_id: RawHyprlandWorkspace::fix_id(raw_monitor.active_workspace.id),
+                // Named workspaces aren't tracked
+                active_workspace_id: RawHyprlandWorkspace::fix_id(raw_monitor.active_workspace.id)
+                    .ok(),
+                active_special_workspace: raw_monitor
+                    .special_workspace
+                    .filter(|ws| ws.id.is_negative())
+                    .map(|ws| ws.name),
             };
             monitors.insert(connector, processed_monitor);
         }
 
-        monitors
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct HyprlandMonitor {
-    pub connector: MonitorConnector,
-    pub active: bool,
-    pub active_workspace_id: WorkspaceId,
-}
-
-impl WaylandCompositorMonitor for HyprlandMonitor {
-    fn connector(&self) -> &str {
-        &self.connector
+        Ok(monitors)
     }
 }
 
@@ -78,50 +91,57 @@ pub struct RawHyprlandWorkspace {
 
     #[serde(rename = "lastwindow")]
     pub active_window_id: String,
+
+    #[serde(rename = "windows")]
+    pub n_windows: usize,
 }
 
 impl RawHyprlandWorkspace {
     /// Converts a 1-based isize to a 0-based usize
-    fn fix_id(one_based_id: isize) -> usize {
-        (one_based_id as usize) - 1
+    fn fix_id(one_based_id: isize) -> Result<WorkspaceId> {
+        usize::try_from(one_based_id)
+            .ok()
+            .and_then(|id| id.checked_sub(1))
+            .ok_or_else(|| anyhow!("invalid workspace id: {one_based_id}"))
     }
 
-    fn postprocess(raw: Vec<RawHyprlandWorkspace>) -> BTreeMap<WorkspaceId, HyprlandWorkspace> {
+    fn postprocess(raw: Vec<RawHyprlandWorkspace>) -> Result<BTreeMap<WorkspaceId, Workspace>> {
         let mut workspaces = BTreeMap::new();
 
         for raw_workspace in raw {
-            // Filter special workspace
+            // Filter special and named workspaces
             if raw_workspace.id.is_negative() {
                 continue;
             }
 
-            let processed_workspace = HyprlandWorkspace {
-                id: Self::fix_id(raw_workspace.id),
+            // Hyprland reports 0x0 when the workspace has no windows
+            let active_window_id =
+                RawHyprlandWindow::fix_id_prefixed(&raw_workspace.active_window_id)?;
+            let processed_workspace = Workspace {
+                id: Self::fix_id(raw_workspace.id)?,
                 name: raw_workspace.name,
                 monitor_connector: raw_workspace.monitor_connector,
-                active_window_id: RawHyprlandWindow::fix_id_prefixed(
-                    raw_workspace.active_window_id,
-                ),
+                active_window_id: Some(active_window_id).filter(|id| *id != 0),
             };
 
             workspaces.insert(processed_workspace.id, processed_workspace);
         }
 
-        workspaces
+        Ok(workspaces)
     }
-}
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct HyprlandWorkspace {
-    pub id: WorkspaceId,
-    pub name: String,
-    pub monitor_connector: String,
-    pub active_window_id: WindowId,
-}
-
-impl WaylandCompositorWorkspace for HyprlandWorkspace {
-    fn name(&self) -> &str {
-        &self.name
+    fn postprocess_special(raw: &[RawHyprlandWorkspace]) -> BTreeMap<String, SpecialWorkspace> {
+        raw.iter()
+            .filter(|raw_workspace| is_special(&raw_workspace.name))
+            .map(|raw_workspace| {
+                let special_workspace = SpecialWorkspace {
+                    name: raw_workspace.name.clone(),
+                    n_windows: raw_workspace.n_windows,
+                    urgent: false,
+                };
+                (special_workspace.name.clone(), special_workspace)
+            })
+            .collect()
     }
 }
 
@@ -135,145 +155,675 @@ pub struct RawHyprlandWindow {
 }
 
 impl RawHyprlandWindow {
-    fn fix_id_prefixed(prefixed_hex_id: String) -> WindowId {
-        usize::from_str_radix(&prefixed_hex_id[2..], 16).expect("failed to parse window id hex")
+    fn fix_id_prefixed(prefixed_hex_id: &str) -> Result<WindowId> {
+        let hex_id = prefixed_hex_id
+            .strip_prefix("0x")
+            .ok_or_else(|| anyhow!("invalid window address: {prefixed_hex_id}"))?;
+        Self::fix_id(hex_id)
     }
 
-    fn fix_id(hex_id: String) -> WindowId {
-        usize::from_str_radix(&hex_id, 16).expect("failed to parse window id hex")
+    fn fix_id(hex_id: &str) -> Result<WindowId> {
+        usize::from_str_radix(hex_id, 16).map_err(|_| anyhow!("invalid window address: {hex_id}"))
     }
 
-    fn postprocess(raw: Vec<RawHyprlandWindow>) -> BTreeMap<WindowId, HyprlandWindow> {
+    fn postprocess(raw: Vec<RawHyprlandWindow>) -> Result<BTreeMap<WindowId, Window>> {
         let mut windows = BTreeMap::new();
 
         for raw_window in raw {
-            // Filter windows on special workspace
+            // Filter windows on special and named workspaces
             if raw_window.workspace.id.is_negative() {
                 continue;
             }
 
-            let processed_window = HyprlandWindow {
-                id: Self::fix_id_prefixed(raw_window.id),
+            let processed_window = Window {
+                id: Self::fix_id_prefixed(&raw_window.id)?,
                 class: raw_window.class,
                 title: raw_window.title,
-                workspace_id: RawHyprlandWorkspace::fix_id(raw_window.workspace.id),
+                workspace_id: RawHyprlandWorkspace::fix_id(raw_window.workspace.id)?,
+                urgent: false,
             };
 
             windows.insert(processed_window.id, processed_window);
         }
 
-        windows
+        Ok(windows)
+    }
+
+    /// Returns the name of the special workspace each window on one is on.
+    fn postprocess_special(raw: &[RawHyprlandWindow]) -> Result<BTreeMap<WindowId, String>> {
+        raw.iter()
+            .filter(|raw_window| is_special(&raw_window.workspace.name))
+            .map(|raw_window| {
+                let id = Self::fix_id_prefixed(&raw_window.id)?;
+                Ok((id, raw_window.workspace.name.clone()))
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct HyprlandWindow {
-    pub id: WindowId,
-    pub class: String,
-    pub title: String,
-    pub workspace_id: WorkspaceId,
+/// Special workspaces are named `special` or `special:<name>`. Named workspaces also have negative
+/// ids, so the id alone can't tell them apart.
+fn is_special(workspace_name: &str) -> bool {
+    workspace_name == "special" || workspace_name.starts_with("special:")
 }
 
-impl WaylandCompositorWindow for HyprlandWindow {
-    fn class(&self) -> &str {
-        &self.class
-    }
+/// Workspace referred to by an event. Events without a `v2` suffix only include its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyprlandEventWorkspace {
+    pub id: Option<isize>,
+    pub name: String,
+}
+
+/// Events from the event socket which affect the state, see https://wiki.hyprland.org/IPC/.
+/// Hyprland sends both versions of events which have a `v2` variant, applying either version has
+/// the same effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HyprlandEvent {
+    /// The active workspace of the focused monitor changed.
+    Workspace(HyprlandEventWorkspace),
+    FocusedMonitor {
+        connector: MonitorConnector,
+        workspace: HyprlandEventWorkspace,
+    },
+    CreateWorkspace(HyprlandEventWorkspace),
+    DestroyWorkspace(HyprlandEventWorkspace),
+    MoveWorkspace {
+        workspace: HyprlandEventWorkspace,
+        connector: MonitorConnector,
+    },
+    RenameWorkspace {
+        id: isize,
+        name: String,
+    },
+    /// The special workspace shown on a monitor changed, the name is empty when it was hidden.
+    ActiveSpecial {
+        name: String,
+        connector: MonitorConnector,
+    },
+    OpenWindow {
+        id: WindowId,
+        workspace: HyprlandEventWorkspace,
+        class: String,
+        title: String,
+    },
+    CloseWindow(WindowId),
+    MoveWindow {
+        id: WindowId,
+        workspace: HyprlandEventWorkspace,
+    },
+    ActiveWindow(Option<WindowId>),
+    WindowTitle {
+        id: WindowId,
+        title: String,
+    },
+    UrgentWindow(WindowId),
+    /// The keybind submap changed, the name is empty when it was reset to the default one.
+    Submap(String),
+    Screencast(Screencast),
+    /// A monitor was added or removed, which is not tracked incrementally.
+    MonitorsChanged,
+}
 
-    fn title(&self) -> &str {
-        &self.title
+impl HyprlandEvent {
+    /// Parses a line from the event socket. Returns `None` for events which don't affect the state.
+    fn parse(line: &str) -> Result<Option<Self>> {
+        let malformed_err = || anyhow!("malformed hyprland socket message: {line}");
+        let (key, value) = line.split_once(">>").ok_or_else(malformed_err)?;
+        let workspace_id = |id: &str| id.parse::<isize>().map_err(|_| malformed_err());
+        let named = |name: &str| HyprlandEventWorkspace {
+            id: None,
+            name: name.to_owned(),
+        };
+
+        let event = match key {
+            "workspace" => Self::Workspace(named(value)),
+            "workspacev2" => {
+                let [id, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::Workspace(HyprlandEventWorkspace {
+                    id: Some(workspace_id(id)?),
+                    name: name.to_owned(),
+                })
+            }
+            "focusedmon" => {
+                let [connector, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::FocusedMonitor {
+                    connector: connector.to_owned(),
+                    workspace: named(name),
+                }
+            }
+            "createworkspace" => Self::CreateWorkspace(named(value)),
+            "createworkspacev2" => {
+                let [id, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::CreateWorkspace(HyprlandEventWorkspace {
+                    id: Some(workspace_id(id)?),
+                    name: name.to_owned(),
+                })
+            }
+            "destroyworkspace" => Self::DestroyWorkspace(named(value)),
+            "destroyworkspacev2" => {
+                let [id, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::DestroyWorkspace(HyprlandEventWorkspace {
+                    id: Some(workspace_id(id)?),
+                    name: name.to_owned(),
+                })
+            }
+            "moveworkspace" => {
+                let [name, connector] = split_args(value).ok_or_else(malformed_err)?;
+                Self::MoveWorkspace {
+                    workspace: named(name),
+                    connector: connector.to_owned(),
+                }
+            }
+            "moveworkspacev2" => {
+                let [id, name, connector] = split_args(value).ok_or_else(malformed_err)?;
+                Self::MoveWorkspace {
+                    workspace: HyprlandEventWorkspace {
+                        id: Some(workspace_id(id)?),
+                        name: name.to_owned(),
+                    },
+                    connector: connector.to_owned(),
+                }
+            }
+            "renameworkspace" => {
+                let [id, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::RenameWorkspace {
+                    id: workspace_id(id)?,
+                    name: name.to_owned(),
+                }
+            }
+            "activespecial" => {
+                let [name, connector] = split_args(value).ok_or_else(malformed_err)?;
+                Self::ActiveSpecial {
+                    name: name.to_owned(),
+                    connector: connector.to_owned(),
+                }
+            }
+            "openwindow" => {
+                let [id, name, class, title] = split_args(value).ok_or_else(malformed_err)?;
+                Self::OpenWindow {
+                    id: RawHyprlandWindow::fix_id(id)?,
+                    workspace: named(name),
+                    class: class.to_owned(),
+                    title: title.to_owned(),
+                }
+            }
+            "closewindow" => Self::CloseWindow(RawHyprlandWindow::fix_id(value)?),
+            "movewindow" => {
+                let [id, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::MoveWindow {
+                    id: RawHyprlandWindow::fix_id(id)?,
+                    workspace: named(name),
+                }
+            }
+            "movewindowv2" => {
+                let [id, workspace, name] = split_args(value).ok_or_else(malformed_err)?;
+                Self::MoveWindow {
+                    id: RawHyprlandWindow::fix_id(id)?,
+                    workspace: HyprlandEventWorkspace {
+                        id: Some(workspace_id(workspace)?),
+                        name: name.to_owned(),
+                    },
+                }
+            }
+            // The value is "," when no window is focused
+            "activewindowv2" => match value {
+                "" | "," => Self::ActiveWindow(None),
+                id => Self::ActiveWindow(Some(RawHyprlandWindow::fix_id(id)?)),
+            },
+            "windowtitlev2" => {
+                let [id, title] = split_args(value).ok_or_else(malformed_err)?;
+                Self::WindowTitle {
+                    id: RawHyprlandWindow::fix_id(id)?,
+                    title: title.to_owned(),
+                }
+            }
+            "urgent" => Self::UrgentWindow(RawHyprlandWindow::fix_id(value)?),
+            "monitoradded" | "monitoraddedv2" | "monitorremoved" => Self::MonitorsChanged,
+            "screencast" => {
+                let [active, owner] = split_args(value).ok_or_else(malformed_err)?;
+                let owner = match owner {
+                    "0" => ScreencastOwner::Monitor,
+                    "1" => ScreencastOwner::Window,
+                    _ => return Err(malformed_err()),
+                };
+                Self::Screencast(Screencast {
+                    active: active == "1",
+                    owner,
+                })
+            }
+            "submap" => Self::Submap(value.to_owned()),
+            "activewindow" | "windowtitle" => {
+                // Intentionally ignored, the v2 events are used instead
+                return Ok(None);
+            }
+            _ => {
+                trace!({ key, value }, "unhandled hyprland socket event");
+                return Ok(None);
+            }
+        };
+        Ok(Some(event))
     }
 }
 
+/// Splits the comma separated arguments of an event. The last argument, often a title, may contain
+/// commas.
+fn split_args<const N: usize>(value: &str) -> Option<[&str; N]> {
+    let args: Vec<&str> = value.splitn(N, ',').collect();
+    args.try_into().ok()
+}
+
+enum ResolvedWorkspace {
+    Regular(WorkspaceId),
+    Special(String),
+    /// Named workspaces aren't tracked, their ids don't correspond to a position.
+    Named,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct HyprlandReducer {
     initialized: bool,
-    monitors: BTreeMap<MonitorConnector, HyprlandMonitor>,
-    workspaces: BTreeMap<WorkspaceId, HyprlandWorkspace>,
-    windows: BTreeMap<WindowId, HyprlandWindow>,
-    active_monitor_connector: MonitorConnector,
-    active_workspace_id: WorkspaceId,
+    /// Set when the event socket disconnects, until the state is refreshed after reconnecting.
+    disconnected: bool,
+    monitors: BTreeMap<MonitorConnector, Monitor>,
+    workspaces: BTreeMap<WorkspaceId, Workspace>,
+    windows: BTreeMap<WindowId, Window>,
+    special_workspaces: BTreeMap<String, SpecialWorkspace>,
+    /// Special workspace names of the windows which are on one.
+    special_windows: BTreeMap<WindowId, String>,
+    /// Hyprland only reports when a window becomes urgent, it stays urgent until it is focused.
+    urgent_window_ids: BTreeSet<WindowId>,
+    active_workspace_id: Option<WorkspaceId>,
     active_window_ids: BTreeMap<WorkspaceId, WindowId>,
+    submap: Option<String>,
+    /// Only reported by events, so it is unknown until the first screencast starts.
+    screencast: Screencast,
+    /// Events received before the state was refreshed, applied once it is. The refresh may or may
+    /// not include their changes, applying them again is harmless.
+    pending_events: Vec<HyprlandEvent>,
 }
 
-impl WaylandCompositor for HyprlandReducer {
-    type Monitor = HyprlandMonitor;
-    type Workspace = HyprlandWorkspace;
-    type Window = HyprlandWindow;
+impl HyprlandReducer {
+    /// Replaces the state. It is left unchanged if the raw state is invalid.
+    fn refresh(
+        &mut self,
+        monitors: Vec<RawHyprlandMonitor>,
+        workspaces: Vec<RawHyprlandWorkspace>,
+        windows: Vec<RawHyprlandWindow>,
+    ) -> Result<()> {
+        let special_workspaces = RawHyprlandWorkspace::postprocess_special(&workspaces);
+        let special_windows = RawHyprlandWindow::postprocess_special(&windows)?;
+        let monitors = RawHyprlandMonitor::postprocess(monitors)?;
+        let workspaces = RawHyprlandWorkspace::postprocess(workspaces)?;
+        let windows = RawHyprlandWindow::postprocess(windows)?;
+
+        self.special_workspaces = special_workspaces;
+        self.special_windows = special_windows;
+        self.monitors = monitors;
+        self.workspaces = workspaces;
+        self.windows = windows;
+        self.active_workspace_id = self.active_monitor().and_then(|m| m.active_workspace_id);
+        self.active_window_ids = self
+            .workspaces
+            .values()
+            .filter_map(|ws| Some((ws.id, ws.active_window_id?)))
+            .collect();
+
+        self.urgent_window_ids
+            .retain(|id| self.windows.contains_key(id) || self.special_windows.contains_key(id));
+        self.update_urgency();
+        self.initialized = true;
+        self.disconnected = false;
+        Ok(())
+    }
 
-    fn monitors(&self) -> &BTreeMap<MonitorConnector, HyprlandMonitor> {
-        &self.monitors
+    /// Finds the workspace an event refers to. Workspaces are found by name when the event doesn't
+    /// include the id, or by number when they don't exist yet. Other unknown names are assumed to
+    /// be named workspaces.
+    fn resolve(&self, workspace: &HyprlandEventWorkspace) -> Option<ResolvedWorkspace> {
+        if is_special(&workspace.name) {
+            return Some(ResolvedWorkspace::Special(workspace.name.clone()));
+        }
+        match workspace.id {
+            Some(id) if id < 0 => return Some(ResolvedWorkspace::Named),
+            Some(id) => {
+                return RawHyprlandWorkspace::fix_id(id)
+                    .ok()
+                    .map(ResolvedWorkspace::Regular)
+            }
+            None => {}
+        }
+
+        let by_name = self
+            .workspaces
+            .values()
+            .find(|ws| ws.name == workspace.name)
+            .map(|ws| ws.id);
+        if let Some(id) = by_name {
+            return Some(ResolvedWorkspace::Regular(id));
+        }
+        match workspace.name.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).map(ResolvedWorkspace::Regular),
+            Err(_) => Some(ResolvedWorkspace::Named),
+        }
     }
 
-    fn active_monitor(&self) -> &Self::Monitor {
-        self.monitors
-            .get(&self.active_monitor_connector)
-            .expect("active monitor not found")
+    /// Applies an event to the state. Returns false if the state doesn't agree with the event, e.g.
+    /// it refers to an unknown workspace, in which case the state should be refreshed.
+    fn apply(&mut self, event: HyprlandEvent) -> bool {
+        use ResolvedWorkspace::{Named, Regular, Special};
+
+        match event {
+            HyprlandEvent::Workspace(workspace) => {
+                // No tracked workspace is active while a named one is shown
+                let id = match self.resolve(&workspace) {
+                    Some(Regular(id)) => Some(id),
+                    Some(Named) => None,
+                    Some(Special(_)) | None => return false,
+                };
+                let Some(monitor) = self.monitors.values_mut().find(|m| m.active) else {
+                    return false;
+                };
+                if id.is_some_and(|id| !self.workspaces.contains_key(&id)) {
+                    return false;
+                }
+                monitor.active_workspace_id = id;
+                self.active_workspace_id = id;
+            }
+            HyprlandEvent::FocusedMonitor {
+                connector,
+                workspace,
+            } => {
+                let id = match self.resolve(&workspace) {
+                    Some(Regular(id)) => Some(id),
+                    Some(Named) => None,
+                    Some(Special(_)) | None => return false,
+                };
+                if !self.monitors.contains_key(&connector)
+                    || id.is_some_and(|id| !self.workspaces.contains_key(&id))
+                {
+                    return false;
+                }
+                for monitor in self.monitors.values_mut() {
+                    monitor.active = monitor.connector == connector;
+                    if monitor.active {
+                        monitor.active_workspace_id = id;
+                    }
+                }
+                self.active_workspace_id = id;
+            }
+            HyprlandEvent::CreateWorkspace(workspace) => match self.resolve(&workspace) {
+                // Workspaces are created on the focused monitor, unless a rule says otherwise in
+                // which case a moveworkspace event follows
+                Some(Regular(id)) => {
+                    let Some(monitor) = self.active_monitor() else {
+                        return false;
+                    };
+                    let monitor_connector = monitor.connector.clone();
+                    self.workspaces.entry(id).or_insert(Workspace {
+                        id,
+                        name: workspace.name,
+                        monitor_connector,
+                        active_window_id: None,
+                    });
+                }
+                Some(Special(name)) => {
+                    self.special_workspaces
+                        .entry(name.clone())
+                        .or_insert(SpecialWorkspace {
+                            name,
+                            n_windows: 0,
+                            urgent: false,
+                        });
+                }
+                Some(Named) => {}
+                None => return false,
+            },
+            HyprlandEvent::DestroyWorkspace(workspace) => match self.resolve(&workspace) {
+                // Workspaces are destroyed once they are empty, so there are no windows to remove
+                Some(Regular(id)) => {
+                    self.workspaces.remove(&id);
+                    self.active_window_ids.remove(&id);
+                }
+                Some(Special(name)) => {
+                    self.special_workspaces.remove(&name);
+                }
+                // The other version of the event already destroyed it
+                Some(Named) | None => {}
+            },
+            HyprlandEvent::MoveWorkspace {
+                workspace,
+                connector,
+            } => {
+                let id = match self.resolve(&workspace) {
+                    Some(Regular(id)) => id,
+                    Some(Named) => return true,
+                    Some(Special(_)) | None => return false,
+                };
+                let Some(workspace) = self.workspaces.get_mut(&id) else {
+                    return false;
+                };
+                if workspace.monitor_connector == connector {
+                    return true;
+                }
+                if !self.monitors.contains_key(&connector) {
+                    return false;
+                }
+
+                // When the workspace was active, the monitor it was on now shows a workspace which
+                // the event doesn't mention.
+                let was_active = self
+                    .monitors
+                    .get(&workspace.monitor_connector)
+                    .is_some_and(|m| m.active_workspace_id == Some(id));
+                if was_active {
+                    return false;
+                }
+                workspace.monitor_connector = connector;
+            }
+            HyprlandEvent::RenameWorkspace { id, name } => {
+                // Named workspaces aren't tracked
+                if id < 0 {
+                    return true;
+                }
+                let Ok(id) = RawHyprlandWorkspace::fix_id(id) else {
+                    return false;
+                };
+                let Some(workspace) = self.workspaces.get_mut(&id) else {
+                    return false;
+                };
+                workspace.name = name;
+            }
+            HyprlandEvent::ActiveSpecial { name, connector } => {
+                let Some(monitor) = self.monitors.get_mut(&connector) else {
+                    return false;
+                };
+                monitor.active_special_workspace = Some(name).filter(|name| !name.is_empty());
+            }
+            HyprlandEvent::OpenWindow {
+                id,
+                workspace,
+                class,
+                title,
+            } => match self.resolve(&workspace) {
+                Some(Regular(workspace_id)) => {
+                    if !self.workspaces.contains_key(&workspace_id) {
+                        return false;
+                    }
+                    let window = Window {
+                        id,
+                        class,
+                        title,
+                        workspace_id,
+                        urgent: false,
+                    };
+                    self.windows.insert(id, window);
+                }
+                Some(Special(name)) => {
+                    let Some(special_workspace) = self.special_workspaces.get_mut(&name) else {
+                        return false;
+                    };
+                    // The window may already be counted when the event is applied after a refresh
+                    if self.special_windows.insert(id, name).is_none() {
+                        special_workspace.n_windows += 1;
+                    }
+                }
+                Some(Named) => {}
+                None => return false,
+            },
+            HyprlandEvent::CloseWindow(id) => {
+                if let Some(window) = self.windows.remove(&id) {
+                    self.forget_active_window(window.workspace_id, id);
+                } else if let Some(name) = self.special_windows.remove(&id) {
+                    if let Some(special_workspace) = self.special_workspaces.get_mut(&name) {
+                        special_workspace.n_windows = special_workspace.n_windows.saturating_sub(1);
+                    }
+                }
+                self.urgent_window_ids.remove(&id);
+            }
+            HyprlandEvent::MoveWindow { id, workspace } => match self.resolve(&workspace) {
+                Some(Regular(workspace_id)) => {
+                    if !self.workspaces.contains_key(&workspace_id) {
+                        return false;
+                    }
+                    let Some(window) = self.windows.get_mut(&id) else {
+                        return false;
+                    };
+                    let previous_workspace_id = window.workspace_id;
+                    window.workspace_id = workspace_id;
+                    if previous_workspace_id != workspace_id {
+                        self.forget_active_window(previous_workspace_id, id);
+                    }
+                }
+                // Windows on named workspaces aren't tracked
+                Some(Named) => {
+                    if let Some(window) = self.windows.remove(&id) {
+                        self.forget_active_window(window.workspace_id, id);
+                    }
+                }
+                // Special windows aren't tracked in enough detail to move them between workspaces
+                Some(Special(_)) | None => return false,
+            },
+            HyprlandEvent::ActiveWindow(Some(window_id)) => {
+                let workspace_id = match self.windows.get(&window_id) {
+                    Some(window) => Some(window.workspace_id),
+                    None => self.active_workspace_id,
+                };
+                if let Some(workspace_id) = workspace_id {
+                    self.active_window_ids.insert(workspace_id, window_id);
+                    if let Some(workspace) = self.workspaces.get_mut(&workspace_id) {
+                        workspace.active_window_id = Some(window_id);
+                    }
+                }
+                self.urgent_window_ids.remove(&window_id);
+            }
+            HyprlandEvent::ActiveWindow(None) => {}
+            HyprlandEvent::WindowTitle { id, title } => {
+                if let Some(window) = self.windows.get_mut(&id) {
+                    window.title = title;
+                }
+            }
+            HyprlandEvent::UrgentWindow(id) => {
+                self.urgent_window_ids.insert(id);
+            }
+            HyprlandEvent::Submap(name) => {
+                self.submap = Some(name).filter(|name| !name.is_empty());
+            }
+            HyprlandEvent::Screencast(screencast) => {
+                self.screencast = screencast;
+            }
+            HyprlandEvent::MonitorsChanged => return false,
+        }
+
+        self.update_urgency();
+        true
+    }
+
+    /// Unsets the active window of a workspace if it is the given window.
+    fn forget_active_window(&mut self, workspace_id: WorkspaceId, window_id: WindowId) {
+        if let Some(workspace) = self.workspaces.get_mut(&workspace_id) {
+            if workspace.active_window_id == Some(window_id) {
+                workspace.active_window_id = None;
+            }
+        }
+        if self.active_window_ids.get(&workspace_id) == Some(&window_id) {
+            self.active_window_ids.remove(&workspace_id);
+        }
+    }
+
+    /// Marks urgent windows and the special workspaces which contain them.
+    fn update_urgency(&mut self) {
+        for window in self.windows.values_mut() {
+            window.urgent = self.urgent_window_ids.contains(&window.id);
+        }
+        for special_workspace in self.special_workspaces.values_mut() {
+            special_workspace.urgent = self.special_windows.iter().any(|(id, name)| {
+                *name == special_workspace.name && self.urgent_window_ids.contains(id)
+            });
+        }
     }
+}
 
-    fn monitor_is_empty(&self, _monitor: &Self::Monitor) -> bool {
-        false // TODO verify correctness
+impl WaylandCompositor for HyprlandReducer {
+    fn monitors(&self) -> &BTreeMap<MonitorConnector, Monitor> {
+        &self.monitors
     }
 
-    fn workspaces(&self) -> &BTreeMap<usize, Self::Workspace> {
+    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Workspace> {
         &self.workspaces
     }
 
-    fn active_workspace(&self, monitor: &Self::Monitor) -> Option<&Self::Workspace> {
-        self.workspaces.get(&monitor.active_workspace_id)
+    fn windows(&self) -> &BTreeMap<WindowId, Window> {
+        &self.windows
     }
 
-    fn workspace_is_empty(&self, workspace: &Self::Workspace) -> bool {
-        self.windows
-            .values()
-            .filter(|w| w.workspace_id == workspace.id)
-            .count()
-            == 0
+    fn special_workspaces(&self) -> &BTreeMap<String, SpecialWorkspace> {
+        &self.special_workspaces
     }
 
-    fn workspaces_in_monitor(&self, monitor: &Self::Monitor) -> Vec<&Self::Workspace> {
-        self.workspaces
-            .values()
-            .filter(|ws| ws.monitor_connector == monitor.connector)
-            .collect()
+    fn submap(&self) -> Option<&str> {
+        self.submap.as_deref()
     }
 
-    fn windows(&self) -> &BTreeMap<usize, Self::Window> {
-        &self.windows
+    fn connected(&self) -> bool {
+        !self.disconnected
     }
 
-    fn active_window(&self, workspace: &Self::Workspace) -> Option<&Self::Window> {
-        self.windows.get(&workspace.active_window_id)
+    fn screencast(&self) -> Screencast {
+        self.screencast
     }
 
-    fn windows_in_workspace(&self, workspace: &Self::Workspace) -> Vec<&Self::Window> {
-        self.windows
-            .values()
-            .filter(|w| w.workspace_id == workspace.id)
-            .collect()
+    fn dispatch(&self, action: Action) {
+        let dispatcher = match action {
+            Action::FocusWorkspace(id) => format!("workspace {}", id + 1),
+            Action::MoveWindowToWorkspace(id) => format!("movetoworkspacesilent {}", id + 1),
+            Action::FocusWindow(id) => format!("focuswindow address:0x{id:x}"),
+            Action::CloseWindow(id) => format!("closewindow address:0x{id:x}"),
+        };
+        task::spawn(async move {
+            if let Err(err) = dispatch(&dispatcher).await {
+                error!("hyprland dispatch failed: {err}");
+            }
+        });
     }
 }
 
 pub enum HyprlandInput {
     RequestRefresh,
     Refresh(
-        BTreeMap<MonitorConnector, HyprlandMonitor>,
-        BTreeMap<WorkspaceId, HyprlandWorkspace>,
-        BTreeMap<WindowId, HyprlandWindow>,
+        Vec<RawHyprlandMonitor>,
+        Vec<RawHyprlandWorkspace>,
+        Vec<RawHyprlandWindow>,
     ),
-    CloseWindow(WindowId),
-    ActiveWindow(usize),
+    Event(HyprlandEvent),
+    /// The event socket disconnected or could not be connected to.
+    Disconnected,
 }
 
 impl Reducible for HyprlandReducer {
     type Input = HyprlandInput;
 
     fn init() -> Self {
-        task::spawn(async move {
-            if let Err(err) = connect_event_socket().await {
-                error!("hyprland event socket connection failed: {err}");
-            }
-        });
-        REDUCER.emit(HyprlandInput::RequestRefresh);
+        task::spawn(supervise());
         Self::default()
     }
 
@@ -287,33 +837,43 @@ impl Reducible for HyprlandReducer {
                 });
             }
             HyprlandInput::Refresh(monitors, workspaces, windows) => {
-                self.monitors = monitors;
-                self.workspaces = workspaces;
-                self.windows = windows;
-                self.active_monitor_connector = self
-                    .monitors
-                    .values()
-                    .find(|m| m.active)
-                    .expect("no active monitor found")
-                    .connector
-                    .clone();
-                self.active_workspace_id = self.active_monitor().active_workspace_id;
-                self.active_window_ids = self
-                    .workspaces
-                    .values()
-                    .map(|ws| (ws.id, ws.active_window_id.clone()))
-                    .collect();
-                self.initialized = true;
-            }
-            HyprlandInput::ActiveWindow(window_id) if self.initialized => {
-                self.active_window_ids
-                    .insert(self.active_workspace_id, window_id);
-            }
-            HyprlandInput::CloseWindow(window_id) if self.initialized => {
-                self.windows.remove(&window_id);
-            }
-
-            _ => {}
+                if let Err(err) = self.refresh(monitors, workspaces, windows) {
+                    error!("hyprland reported invalid state: {err}");
+                    return true;
+                }
+                for event in mem::take(&mut self.pending_events) {
+                    self.reduce(HyprlandInput::Event(event));
+                }
+            }
+            HyprlandInput::Event(event) if self.initialized => {
+                let consistent = self.apply(event.clone());
+                if !consistent {
+                    debug!(
+                        ?event,
+                        "hyprland state is inconsistent with event, refreshing"
+                    );
+                    self.reduce(HyprlandInput::RequestRefresh);
+                }
+            }
+            HyprlandInput::Event(event) => {
+                if self.pending_events.len() < MAX_PENDING_EVENTS {
+                    self.pending_events.push(event);
+                } else {
+                    debug!(
+                        ?event,
+                        "too many events before hyprland state was refreshed"
+                    );
+                }
+            }
+            HyprlandInput::Disconnected => {
+                // Events are held until the state is refreshed after reconnecting. Only events
+                // report the submap and screencast, so they can't be trusted any more.
+                self.initialized = false;
+                self.disconnected = true;
+                self.submap = None;
+                self.screencast = Screencast::default();
+                self.pending_events.clear();
+            }
         }
         true
     }
@@ -322,29 +882,58 @@ impl Reducible for HyprlandReducer {
 async fn refresh() -> Result<()> {
     let monitors = send(b"[j]/monitors").await?;
     let monitors: Vec<RawHyprlandMonitor> = serde_json::from_slice(&monitors)?;
-    let monitors = RawHyprlandMonitor::postprocess(monitors);
 
     let workspaces = send(b"[j]/workspaces").await?;
     let workspaces: Vec<RawHyprlandWorkspace> = serde_json::from_slice(&workspaces)?;
-    let workspaces = RawHyprlandWorkspace::postprocess(workspaces);
 
     let windows = send(b"[j]/clients").await?;
     let windows: Vec<RawHyprlandWindow> = serde_json::from_slice(&windows)?;
-    let windows = RawHyprlandWindow::postprocess(windows);
 
     REDUCER.emit(HyprlandInput::Refresh(monitors, workspaces, windows));
     Ok(())
 }
 
-async fn control_socket_stream() -> Result<UnixStream> {
-    let hyprland_instance_signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
-    let socket = format!("/tmp/hypr/{hyprland_instance_signature}/.socket.sock");
-    let stream = UnixStream::connect(socket).await?;
-    Ok(stream)
+/// Returns the directory containing Hyprland's sockets.
+fn socket_dir() -> Result<PathBuf> {
+    if let Some(dir) = &config::get().providers.wayland.hyprland_socket_dir {
+        return Ok(dir.clone());
+    }
+
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .context("HYPRLAND_INSTANCE_SIGNATURE is not set")?;
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+    find_socket_dir(&signature, runtime_dir.as_deref(), Path::new("/tmp"))
+}
+
+/// Finds the socket directory of a Hyprland instance. Hyprland 0.40 moved it from `/tmp/hypr` to
+/// `$XDG_RUNTIME_DIR/hypr`, so both are probed.
+fn find_socket_dir(signature: &str, runtime_dir: Option<&Path>, tmp_dir: &Path) -> Result<PathBuf> {
+    let candidates: Vec<PathBuf> = runtime_dir
+        .into_iter()
+        .chain([tmp_dir])
+        .map(|dir| dir.join("hypr").join(signature))
+        .collect();
+
+    let found = candidates
+        .iter()
+        .find(|dir| dir.join(".socket.sock").exists());
+    found.cloned().ok_or_else(|| {
+        let candidates: Vec<String> = candidates
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        anyhow!("hyprland sockets not found in {}", candidates.join(" or "))
+    })
 }
 
 async fn send(command: &[u8]) -> Result<Vec<u8>> {
-    let mut stream = control_socket_stream().await?;
+    send_to(&socket_dir()?.join(".socket.sock"), command).await
+}
+
+/// Sends a command to the control socket at `path` and reads the reply, which ends when Hyprland
+/// closes the connection.
+async fn send_to(path: &Path, command: &[u8]) -> Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(path).await?;
     stream.writable().await?;
     stream.write_all(command).await?;
 
@@ -362,50 +951,344 @@ async fn send(command: &[u8]) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
-async fn connect_event_socket() -> Result<()> {
-    let hyprland_instance_signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")?;
-    let socket = format!("/tmp/hypr/{hyprland_instance_signature}/.socket2.sock");
-    let stream = UnixStream::connect(socket).await?;
+async fn dispatch(dispatcher: &str) -> Result<()> {
+    let reply = send(format!("dispatch {dispatcher}").as_bytes()).await?;
+    if reply != b"ok" {
+        let reply = String::from_utf8_lossy(&reply);
+        bail!("hyprland rejected \"{dispatcher}\": {reply}");
+    }
+    Ok(())
+}
+
+/// Keeps the event socket connected, reconnecting with exponential backoff when the connection
+/// fails or Hyprland closes it.
+async fn supervise() {
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match connect_event_socket().await {
+            Ok(stream) => {
+                info!("connected to hyprland event socket");
+                delay = MIN_RECONNECT_DELAY;
+                // Events may have been missed while disconnected
+                REDUCER.emit(HyprlandInput::RequestRefresh);
+
+                let result = watch(stream, |event| {
+                    REDUCER.emit(HyprlandInput::Event(event));
+                })
+                .await;
+                match result {
+                    Ok(()) => warn!("hyprland closed the event socket"),
+                    Err(err) => error!("hyprland event socket failed: {err}"),
+                }
+            }
+            Err(err) => error!("hyprland event socket connection failed: {err}"),
+        }
+
+        REDUCER.emit(HyprlandInput::Disconnected);
+        debug!(?delay, "reconnecting to hyprland event socket");
+        sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn connect_event_socket() -> Result<UnixStream> {
+    let socket = socket_dir()?.join(".socket2.sock");
+    Ok(UnixStream::connect(socket).await?)
+}
+
+/// Reads events until Hyprland closes the event socket. Malformed events are logged and skipped.
+async fn watch(stream: impl AsyncRead + Unpin, on_event: impl Fn(HyprlandEvent)) -> Result<()> {
     let reader = BufReader::new(stream);
     let mut lines = reader.lines();
 
-    loop {
-        let Some(line) = lines.next_line().await? else { continue };
-        let malformed_err = || anyhow!("malformed hyprland socket message: {line}");
-        let (key, value) = line.split_once(">>").ok_or_else(malformed_err)?;
+    while let Some(line) = lines.next_line().await? {
+        match HyprlandEvent::parse(&line) {
+            Ok(Some(event)) => on_event(event),
+            Ok(None) => {}
+            Err(err) => warn!("{err}"),
+        }
+    }
+    Ok(())
+}
 
-        match key {
-            "workspace" | "openwindow" | "movewindow" => {
-                refresh().await?;
-            }
-            "activewindowv2" if value != "," => {
-                let window_id = RawHyprlandWindow::fix_id(value.to_owned());
-                REDUCER.emit(HyprlandInput::ActiveWindow(window_id));
-            }
-            "closewindow" => {
-                let window_id = RawHyprlandWindow::fix_id(value.to_owned());
-                REDUCER.emit(HyprlandInput::CloseWindow(window_id));
-            }
-            "moveworkspace" => {
-                // TODO determine if moveworkspace can be handled without needing a refresh
-            }
-            "screencast" => {
-                // TODO screencast state should be added to the trait
-            }
-            "urgent" => {
-                // TODO urgent window should be added to the trait, workspaces component should show
-                // which workspace contains the urgent window
-            }
-            "createworkspace" | "destroyworkspace" | "activewindow" | "activewindowv2"
-            | "submap" => {
-                // Intentionally ignored
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    const MONITORS: &str = include_str!("../../tests/fixtures/hyprland/monitors.json");
+    const WORKSPACES: &str = include_str!("../../tests/fixtures/hyprland/workspaces.json");
+    const CLIENTS: &str = include_str!("../../tests/fixtures/hyprland/clients.json");
+    const MONITORS_FINAL: &str = include_str!("../../tests/fixtures/hyprland/monitors_final.json");
+    const WORKSPACES_FINAL: &str =
+        include_str!("../../tests/fixtures/hyprland/workspaces_final.json");
+    const CLIENTS_FINAL: &str = include_str!("../../tests/fixtures/hyprland/clients_final.json");
+    const EVENTS: &str = include_str!("../../tests/fixtures/hyprland/events.txt");
+    const MONITORS_NAMED: &str = include_str!("../../tests/fixtures/hyprland/monitors_named.json");
+    const WORKSPACES_NAMED: &str =
+        include_str!("../../tests/fixtures/hyprland/workspaces_named.json");
+    const CLIENTS_NAMED: &str = include_str!("../../tests/fixtures/hyprland/clients_named.json");
+
+    fn refreshed(monitors: &str, workspaces: &str, clients: &str) -> HyprlandReducer {
+        let mut state = HyprlandReducer::default();
+        state
+            .refresh(
+                serde_json::from_str(monitors).unwrap(),
+                serde_json::from_str(workspaces).unwrap(),
+                serde_json::from_str(clients).unwrap(),
+            )
+            .unwrap();
+        state
+    }
 
-                // TODO decide which of the remaining events should also be ignored or handled
-                // https://wiki.hyprland.org/IPC/
-            }
-            _ => {
-                trace!({key, value}, "unhandled hyprland socket event");
-            }
+    /// Replays events through a fake event socket, starting from the initial state. Returns the
+    /// resulting state and whether any event required a refresh.
+    async fn replay(events: &'static str) -> (HyprlandReducer, bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".socket2.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(events.as_bytes()).await.unwrap();
+        });
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let state = Mutex::new((refreshed(MONITORS, WORKSPACES, CLIENTS), false));
+        watch(stream, |event| {
+            let (state, needs_refresh) = &mut *state.lock().unwrap();
+            *needs_refresh |= !state.apply(event);
+        })
+        .await
+        .unwrap();
+        state.into_inner().unwrap()
+    }
+
+    #[test]
+    fn parses_events() {
+        assert_eq!(
+            HyprlandEvent::parse("openwindow>>e1,3,mpv,a, b").unwrap(),
+            Some(HyprlandEvent::OpenWindow {
+                id: 0xe1,
+                workspace: HyprlandEventWorkspace {
+                    id: None,
+                    name: "3".into(),
+                },
+                class: "mpv".into(),
+                title: "a, b".into(),
+            })
+        );
+        assert_eq!(
+            HyprlandEvent::parse("activewindowv2>>,").unwrap(),
+            Some(HyprlandEvent::ActiveWindow(None))
+        );
+        assert_eq!(
+            HyprlandEvent::parse("submap>>resize").unwrap(),
+            Some(HyprlandEvent::Submap("resize".into()))
+        );
+        assert_eq!(
+            HyprlandEvent::parse("screencast>>1,1").unwrap(),
+            Some(HyprlandEvent::Screencast(Screencast {
+                active: true,
+                owner: ScreencastOwner::Window,
+            }))
+        );
+        assert_eq!(HyprlandEvent::parse("activewindow>>kitty,~").unwrap(), None);
+        assert!(HyprlandEvent::parse("closewindow>>xyz").is_err());
+        assert!(HyprlandEvent::parse("movewindowv2>>a1").is_err());
+        assert!(HyprlandEvent::parse("garbage").is_err());
+    }
+
+    #[tokio::test]
+    async fn replays_events() {
+        let (state, needs_refresh) = replay(EVENTS).await;
+        assert!(!needs_refresh);
+
+        // Urgency isn't included in the snapshot
+        let mut expected = refreshed(MONITORS_FINAL, WORKSPACES_FINAL, CLIENTS_FINAL);
+        assert!(expected.apply(HyprlandEvent::UrgentWindow(0xb1)));
+
+        assert_eq!(state.monitors(), expected.monitors());
+        assert_eq!(state.workspaces(), expected.workspaces());
+        assert_eq!(state.windows(), expected.windows());
+        assert_eq!(state.special_workspaces(), expected.special_workspaces());
+        assert_eq!(state.submap(), None);
+
+        let (state, _) = replay("submap>>resize\n").await;
+        assert_eq!(state.submap(), Some("resize"));
+    }
+
+    #[tokio::test]
+    async fn tells_named_workspaces_from_special_ones() {
+        let (state, needs_refresh) = replay(
+            "createworkspacev2>>-1338,specialty\ncreateworkspacev2>>-97,special\n\
+             createworkspacev2>>-96,special:music\n",
+        )
+        .await;
+        assert!(!needs_refresh);
+        assert!(!state.special_workspaces().contains_key("specialty"));
+        assert!(state.special_workspaces().contains_key("special"));
+        assert!(state.special_workspaces().contains_key("special:music"));
+    }
+
+    #[tokio::test]
+    async fn ignores_named_workspaces() {
+        let (state, needs_refresh) =
+            replay("createworkspacev2>>-1337,mail\nworkspacev2>>-1337,mail\n").await;
+        assert!(!needs_refresh);
+        assert!(!state.special_workspaces().contains_key("mail"));
+        assert_eq!(state.active_monitor().unwrap().active_workspace_id, None);
+        assert_eq!(state.workspaces().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn resets_on_disconnect() {
+        let (mut state, _) = replay("submap>>resize\nscreencast>>1,0\n").await;
+        assert_eq!(state.submap(), Some("resize"));
+        assert!(state.screencast().active);
+
+        assert!(state.reduce(HyprlandInput::Disconnected));
+        assert!(!state.connected());
+        assert_eq!(state.submap(), None);
+        assert_eq!(state.screencast(), Screencast::default());
+
+        // Events are held until the state is refreshed
+        state.reduce(HyprlandInput::Event(HyprlandEvent::Submap("resize".into())));
+        assert_eq!(state.submap(), None);
+
+        state.reduce(HyprlandInput::Refresh(
+            serde_json::from_str(MONITORS).unwrap(),
+            serde_json::from_str(WORKSPACES).unwrap(),
+            serde_json::from_str(CLIENTS).unwrap(),
+        ));
+        assert!(state.connected());
+        assert_eq!(state.submap(), Some("resize"));
+    }
+
+    #[test]
+    fn applies_events_received_before_refresh() {
+        let mut state = HyprlandReducer::default();
+        for line in [
+            "openwindow>>e1,2,mpv,video.mp4",
+            "openwindow>>d1,special:scratch,pavucontrol,Volume Control",
+            "workspacev2>>2,2",
+        ] {
+            let event = HyprlandEvent::parse(line).unwrap().unwrap();
+            state.reduce(HyprlandInput::Event(event));
+        }
+        assert!(state.windows().is_empty());
+
+        // The snapshot may already include the events, e.g. the window on the special workspace
+        state.reduce(HyprlandInput::Refresh(
+            serde_json::from_str(MONITORS).unwrap(),
+            serde_json::from_str(WORKSPACES).unwrap(),
+            serde_json::from_str(CLIENTS).unwrap(),
+        ));
+        assert_eq!(state.windows()[&0xe1].workspace_id, 1);
+        assert_eq!(state.active_monitor().unwrap().active_workspace_id, Some(1));
+        assert_eq!(state.special_workspaces()["special:scratch"].n_windows, 1);
+    }
+
+    #[test]
+    fn refreshes_with_named_workspace() {
+        let state = refreshed(MONITORS_NAMED, WORKSPACES_NAMED, CLIENTS_NAMED);
+
+        assert_eq!(state.monitors()["HDMI-A-1"].active_workspace_id, None);
+        assert_eq!(
+            state.workspaces(),
+            refreshed(MONITORS, WORKSPACES, CLIENTS).workspaces()
+        );
+        assert!(!state.windows().contains_key(&0xf2));
+        assert!(!state.special_workspaces().contains_key("mail"));
+    }
+
+    #[tokio::test]
+    async fn refreshes_on_inconsistency() {
+        for events in [
+            // Unknown workspace
+            "openwindow>>f1,special:unknown,mpv,video.mp4\n",
+            // Unknown window
+            "movewindowv2>>f1,2,2\n",
+            // The workspace shown on DP-1 instead is unknown
+            "moveworkspacev2>>1,1,HDMI-A-1\n",
+            "monitoradded>>DP-2\n",
+        ] {
+            let (_, needs_refresh) = replay(events).await;
+            assert!(needs_refresh, "{events}");
         }
+
+        // Both versions of events are sent, the second one is a no-op
+        let (state, needs_refresh) = replay("destroyworkspace>>2\ndestroyworkspacev2>>2,2\n").await;
+        assert!(!needs_refresh);
+        assert!(!state.workspaces().contains_key(&1));
+    }
+
+    #[tokio::test]
+    async fn skips_malformed_events() {
+        let (state, needs_refresh) = replay("closewindow>>xyz\ngarbage\nurgent>>b1\n").await;
+        assert!(!needs_refresh);
+        assert!(state.windows()[&0xb1].urgent);
+    }
+
+    #[test]
+    fn rejects_invalid_state() {
+        let mut state = refreshed(MONITORS, WORKSPACES, CLIENTS);
+        let clients = CLIENTS.replace("0xa1", "a1");
+        let result = state.refresh(
+            serde_json::from_str(MONITORS).unwrap(),
+            serde_json::from_str(WORKSPACES).unwrap(),
+            serde_json::from_str(&clients).unwrap(),
+        );
+        assert!(result.is_err());
+        assert!(state.windows().contains_key(&0xa1));
+    }
+
+    #[test]
+    fn finds_socket_dir() {
+        let runtime_dir = tempfile::tempdir().unwrap();
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let bind = |root: &Path| {
+            let dir = root.join("hypr").join("signature");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::os::unix::net::UnixListener::bind(dir.join(".socket.sock")).unwrap()
+        };
+        let find = || find_socket_dir("signature", Some(runtime_dir.path()), tmp_dir.path());
+
+        assert!(find().is_err());
+
+        // Older versions of Hyprland
+        let _legacy = bind(tmp_dir.path());
+        assert_eq!(find().unwrap(), tmp_dir.path().join("hypr/signature"));
+        let dir = find_socket_dir("signature", None, tmp_dir.path()).unwrap();
+        assert_eq!(dir, tmp_dir.path().join("hypr/signature"));
+
+        let _current = bind(runtime_dir.path());
+        assert_eq!(find().unwrap(), runtime_dir.path().join("hypr/signature"));
+    }
+
+    #[tokio::test]
+    async fn sends_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".socket.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        // Longer than a single read
+        let reply = "x".repeat(10_000);
+
+        let expected_reply = reply.clone();
+        task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 64];
+            let n = stream.read(&mut request).await.unwrap();
+            assert_eq!(&request[..n], b"[j]/monitors");
+            for chunk in expected_reply.as_bytes().chunks(3000) {
+                stream.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let received = send_to(&path, b"[j]/monitors").await.unwrap();
+        assert_eq!(received, reply.as_bytes());
     }
 }
@@ -1,3 +1,36 @@
+use std::sync::Arc;
+
+use relm4::Sender;
+
+use crate::{config, data::wayland_compositor::WaylandCompositor};
+
+pub mod ext_workspace;
 pub mod hyprland;
+pub mod niri;
 pub mod openrazer;
+pub mod pipewire;
 pub mod pulseaudio;
+pub mod sway;
+
+/// Subscribes to the compositor backend selected by `providers.wayland.compositor`. Only the
+/// selected backend's reducer is initialized, so only it connects to its compositor.
+pub fn subscribe_to_compositor<Msg, F>(sender: &Sender<Msg>, f: F)
+where
+    F: Fn(Arc<dyn WaylandCompositor>) -> Msg + 'static + Send + Sync,
+    Msg: Send + 'static,
+{
+    match config::get().providers.wayland.compositor {
+        config::Compositor::ExtWorkspace => {
+            ext_workspace::REDUCER.subscribe(sender, move |data| f(Arc::new(data.clone())))
+        }
+        config::Compositor::Hyprland => {
+            hyprland::REDUCER.subscribe(sender, move |data| f(Arc::new(data.clone())))
+        }
+        config::Compositor::Niri => {
+            niri::REDUCER.subscribe(sender, move |data| f(Arc::new(data.clone())))
+        }
+        config::Compositor::Sway => {
+            sway::REDUCER.subscribe(sender, move |data| f(Arc::new(data.clone())))
+        }
+    }
+}
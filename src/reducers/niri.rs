@@ -0,0 +1,499 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use relm4::{Reducer, Reducible};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::UnixStream,
+    task,
+    time::sleep,
+};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::data::wayland_compositor::{
+    Action, Monitor, MonitorConnector, WaylandCompositor, Window, WindowId, Workspace, WorkspaceId,
+};
+
+pub static REDUCER: Reducer<NiriReducer> = Reducer::new();
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawNiriWorkspace {
+    /// Opaque id that niri never reuses, unrelated to the workspace's position.
+    pub id: u64,
+    /// 1-based index of the workspace on its output.
+    pub idx: u8,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    pub is_active: bool,
+    pub is_focused: bool,
+    pub active_window_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawNiriWindow {
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub workspace_id: Option<u64>,
+    /// Only reported by niri 25.05 and newer.
+    #[serde(default)]
+    pub is_urgent: bool,
+}
+
+impl RawNiriWindow {
+    /// Converts to a window, given the ids assigned to niri's workspace ids.
+    fn postprocess(&self, workspace_ids: &BTreeMap<u64, WorkspaceId>) -> Option<Window> {
+        Some(Window {
+            id: self.id as WindowId,
+            class: self.app_id.clone().unwrap_or_default(),
+            title: self.title.clone().unwrap_or_default(),
+            workspace_id: *workspace_ids.get(&self.workspace_id?)?,
+            urgent: self.is_urgent,
+        })
+    }
+}
+
+/// Events from niri's event stream. The stream begins with the full state, e.g. `WorkspacesChanged`
+/// and `WindowsChanged`, followed by incremental changes.
+#[derive(Debug, Clone, Deserialize)]
+pub enum RawNiriEvent {
+    WorkspacesChanged {
+        workspaces: Vec<RawNiriWorkspace>,
+    },
+    WorkspaceActivated {
+        id: u64,
+        focused: bool,
+    },
+    WorkspaceActiveWindowChanged {
+        workspace_id: u64,
+        active_window_id: Option<u64>,
+    },
+    WindowsChanged {
+        windows: Vec<RawNiriWindow>,
+    },
+    WindowOpenedOrChanged {
+        window: RawNiriWindow,
+    },
+    WindowClosed {
+        id: u64,
+    },
+    WindowUrgencyChanged {
+        id: u64,
+        urgent: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NiriReducer {
+    raw_workspaces: BTreeMap<u64, RawNiriWorkspace>,
+    raw_windows: BTreeMap<u64, RawNiriWindow>,
+    /// niri's workspace ids by our ids, which are the workspaces' positions ordered by output and
+    /// index.
+    niri_workspace_ids: BTreeMap<WorkspaceId, u64>,
+    monitors: BTreeMap<MonitorConnector, Monitor>,
+    workspaces: BTreeMap<WorkspaceId, Workspace>,
+    windows: BTreeMap<WindowId, Window>,
+}
+
+impl NiriReducer {
+    fn apply(&mut self, event: RawNiriEvent) {
+        match event {
+            RawNiriEvent::WorkspacesChanged { workspaces } => {
+                self.raw_workspaces = workspaces.into_iter().map(|ws| (ws.id, ws)).collect();
+            }
+            RawNiriEvent::WorkspaceActivated { id, focused } => {
+                let Some(output) = self.raw_workspaces.get(&id).map(|ws| ws.output.clone()) else {
+                    return;
+                };
+                for ws in self.raw_workspaces.values_mut() {
+                    if ws.output == output {
+                        ws.is_active = ws.id == id;
+                    }
+                    if focused {
+                        ws.is_focused = ws.id == id;
+                    }
+                }
+            }
+            RawNiriEvent::WorkspaceActiveWindowChanged {
+                workspace_id,
+                active_window_id,
+            } => {
+                if let Some(ws) = self.raw_workspaces.get_mut(&workspace_id) {
+                    ws.active_window_id = active_window_id;
+                }
+            }
+            RawNiriEvent::WindowsChanged { windows } => {
+                self.raw_windows = windows.into_iter().map(|w| (w.id, w)).collect();
+            }
+            RawNiriEvent::WindowOpenedOrChanged { window } => {
+                self.raw_windows.insert(window.id, window);
+            }
+            RawNiriEvent::WindowClosed { id } => {
+                self.raw_windows.remove(&id);
+            }
+            RawNiriEvent::WindowUrgencyChanged { id, urgent } => {
+                if let Some(window) = self.raw_windows.get_mut(&id) {
+                    window.is_urgent = urgent;
+                }
+            }
+        }
+
+        self.postprocess();
+    }
+
+    /// Rebuilds monitors, workspaces and windows from niri's workspaces and windows, monitors only
+    /// exist in niri as the outputs of workspaces.
+    fn postprocess(&mut self) {
+        self.niri_workspace_ids.clear();
+        self.monitors.clear();
+        self.workspaces.clear();
+
+        // niri's ids only ever increase, so number workspaces by where they are instead
+        let mut raw_workspaces: Vec<_> = self
+            .raw_workspaces
+            .values()
+            .filter_map(|ws| Some((ws.output.as_ref()?, ws)))
+            .collect();
+        raw_workspaces.sort_by_key(|(connector, ws)| (*connector, ws.idx));
+
+        for (id, (connector, raw_workspace)) in raw_workspaces.into_iter().enumerate() {
+            self.niri_workspace_ids.insert(id, raw_workspace.id);
+
+            let monitor = self
+                .monitors
+                .entry(connector.clone())
+                .or_insert_with(|| Monitor {
+                    connector: connector.clone(),
+                    active: false,
+                    active_workspace_id: None,
+                    active_special_workspace: None,
+                });
+            if raw_workspace.is_active {
+                monitor.active_workspace_id = Some(id);
+            }
+            if raw_workspace.is_focused {
+                monitor.active = true;
+            }
+
+            let name = match &raw_workspace.name {
+                Some(name) => name.clone(),
+                None => raw_workspace.idx.to_string(),
+            };
+            self.workspaces.insert(
+                id,
+                Workspace {
+                    id,
+                    name,
+                    monitor_connector: connector.clone(),
+                    active_window_id: raw_workspace.active_window_id.map(|id| id as WindowId),
+                },
+            );
+        }
+
+        let workspace_ids = self
+            .niri_workspace_ids
+            .iter()
+            .map(|(&id, &niri_id)| (niri_id, id))
+            .collect();
+        self.windows = self
+            .raw_windows
+            .values()
+            .filter_map(|window| window.postprocess(&workspace_ids))
+            .map(|window| (window.id, window))
+            .collect();
+    }
+
+    /// Builds the niri request for `action`, or `None` if it refers to a workspace that doesn't
+    /// exist.
+    fn action_request(&self, action: Action) -> Option<serde_json::Value> {
+        let niri_workspace_id = |id| self.niri_workspace_ids.get(&id).copied();
+        let action = match action {
+            Action::FocusWorkspace(id) => json!({
+                "FocusWorkspace": { "reference": { "Id": niri_workspace_id(id)? } }
+            }),
+            Action::MoveWindowToWorkspace(id) => json!({
+                "MoveWindowToWorkspace": {
+                    "window_id": null,
+                    "reference": { "Id": niri_workspace_id(id)? },
+                    "focus": false,
+                }
+            }),
+            Action::FocusWindow(id) => json!({ "FocusWindow": { "id": id } }),
+            Action::CloseWindow(id) => json!({ "CloseWindow": { "id": id } }),
+        };
+        Some(json!({ "Action": action }))
+    }
+}
+
+impl WaylandCompositor for NiriReducer {
+    fn monitors(&self) -> &BTreeMap<MonitorConnector, Monitor> {
+        &self.monitors
+    }
+
+    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Workspace> {
+        &self.workspaces
+    }
+
+    fn windows(&self) -> &BTreeMap<WindowId, Window> {
+        &self.windows
+    }
+
+    fn dispatch(&self, action: Action) {
+        let Some(request_json) = self.action_request(action) else {
+            trace!("ignoring action on a missing niri workspace");
+            return;
+        };
+        task::spawn(async move {
+            if let Err(err) = request(request_json).await {
+                error!("niri action failed: {err}");
+            }
+        });
+    }
+}
+
+pub enum NiriInput {
+    Event(RawNiriEvent),
+    /// The event stream closed or could not be requested.
+    Disconnected,
+}
+
+impl Reducible for NiriReducer {
+    type Input = NiriInput;
+
+    fn init() -> Self {
+        task::spawn(supervise());
+        Self::default()
+    }
+
+    fn reduce(&mut self, input: Self::Input) -> bool {
+        match input {
+            NiriInput::Event(event) => self.apply(event),
+            // niri sends the full state again when the event stream is requested
+            NiriInput::Disconnected => *self = Self::default(),
+        }
+        true
+    }
+}
+
+fn socket_path() -> Result<PathBuf> {
+    env::var_os("NIRI_SOCKET")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("$NIRI_SOCKET is not set"))
+}
+
+/// Sends a request over a new connection to niri and waits for the reply.
+async fn request(request: serde_json::Value) -> Result<serde_json::Value> {
+    let mut stream = UnixStream::connect(socket_path()?).await?;
+    stream.write_all(format!("{request}\n").as_bytes()).await?;
+
+    let reply = BufReader::new(stream)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("niri closed the socket without replying"))?;
+    let reply: Result<serde_json::Value, String> = serde_json::from_str(&reply)?;
+    reply.map_err(|err| anyhow!("niri rejected {request}: {err}"))
+}
+
+async fn supervise() {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(err) => {
+            error!("niri event stream connection failed: {err}");
+            return;
+        }
+    };
+
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match request_event_stream(&path).await {
+            Ok(lines) => {
+                info!("connected to niri event stream");
+                delay = MIN_RECONNECT_DELAY;
+
+                match watch(lines, |event| REDUCER.emit(NiriInput::Event(event))).await {
+                    Ok(()) => warn!("niri closed the event stream"),
+                    Err(err) => error!("niri event stream failed: {err}"),
+                }
+            }
+            Err(err) => error!("niri event stream connection failed: {err}"),
+        }
+
+        REDUCER.emit(NiriInput::Disconnected);
+        debug!(?delay, "reconnecting to niri event stream");
+        sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Requests the event stream from the niri socket at `path`.
+async fn request_event_stream(path: &Path) -> Result<Lines<BufReader<UnixStream>>> {
+    let mut stream = UnixStream::connect(path).await?;
+    stream.write_all(b"\"EventStream\"\n").await?;
+    let mut lines = BufReader::new(stream).lines();
+
+    let reply = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("niri closed the socket without replying"))?;
+    let reply: Result<serde_json::Value, String> = serde_json::from_str(&reply)?;
+    if let Err(err) = reply {
+        bail!("failed to request niri event stream: {err}");
+    }
+    Ok(lines)
+}
+
+/// Calls `on_event` with each event from the stream. Returns when niri closes the stream.
+async fn watch(
+    mut lines: Lines<BufReader<UnixStream>>,
+    on_event: impl Fn(RawNiriEvent),
+) -> Result<()> {
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str(&line) {
+            Ok(event) => on_event(event),
+            Err(_) => trace!({ line }, "unhandled niri event"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    const EVENTS: &str = include_str!("../../tests/fixtures/niri/events.jsonl");
+    const EVENTS_REORDERED: &str = include_str!("../../tests/fixtures/niri/events_reordered.jsonl");
+
+    /// Replays the first `n` recorded events through a fake niri socket and returns the resulting
+    /// state.
+    async fn replay(events: &'static str, n: usize) -> NiriReducer {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("niri.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut lines = BufReader::new(stream);
+            let mut request = String::new();
+            lines.read_line(&mut request).await.unwrap();
+            assert_eq!(request, "\"EventStream\"\n");
+
+            let mut stream = lines.into_inner();
+            stream.write_all(b"{\"Ok\":\"Handled\"}\n").await.unwrap();
+            for event in events.lines().take(n) {
+                stream
+                    .write_all(format!("{event}\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let state = Mutex::new(NiriReducer::default());
+        let lines = request_event_stream(&path).await.unwrap();
+        watch(lines, |event| state.lock().unwrap().apply(event))
+            .await
+            .unwrap();
+        state.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn initial_state() {
+        let state = replay(EVENTS, 2).await;
+
+        let monitor = state.active_monitor().unwrap();
+        assert_eq!(monitor.connector, "DP-1");
+        assert_eq!(monitor.active_workspace_id, Some(0));
+        assert_eq!(state.monitors()["HDMI-A-1"].active_workspace_id, Some(2));
+
+        assert_eq!(
+            state.workspaces().keys().collect::<Vec<_>>(),
+            [&0, &1, &2, &3]
+        );
+        assert_eq!(state.workspaces()[&3].name, "chat");
+        assert_eq!(state.workspaces()[&1].name, "2");
+        assert_eq!(state.windows_in_workspace(&state.workspaces()[&0]).len(), 2);
+        assert!(state.workspace_is_empty(&state.workspaces()[&1]));
+
+        let window = state.active_window(&state.workspaces()[&0]).unwrap();
+        assert_eq!(window.class, "kitty");
+        assert_eq!(window.title, "~");
+    }
+
+    #[tokio::test]
+    async fn incremental_events() {
+        let state = replay(EVENTS, usize::MAX).await;
+
+        let monitor = state.active_monitor().unwrap();
+        assert_eq!(monitor.connector, "DP-1");
+        assert_eq!(monitor.active_workspace_id, Some(1));
+        assert!(!state.monitors()["HDMI-A-1"].active);
+
+        assert_eq!(state.windows().keys().collect::<Vec<_>>(), [&10, &12, &20]);
+        let window = state.active_window(&state.workspaces()[&1]).unwrap();
+        assert_eq!(window.class, "firefox");
+        assert_eq!(window.workspace_id, 1);
+    }
+
+    #[tokio::test]
+    async fn resets_on_disconnect() {
+        let mut state = replay(EVENTS, usize::MAX).await;
+        state.reduce(NiriInput::Disconnected);
+
+        assert!(state.monitors().is_empty());
+        assert!(state.workspaces().is_empty());
+        assert!(state.windows().is_empty());
+        assert_eq!(state.action_request(Action::FocusWorkspace(0)), None);
+    }
+
+    #[tokio::test]
+    async fn numbers_workspaces_by_position() {
+        let state = replay(EVENTS_REORDERED, 2).await;
+
+        let names: Vec<_> = state.workspaces().values().map(|ws| &ws.name).collect();
+        assert_eq!(names, ["1", "2", "1"]);
+        assert_eq!(state.active_monitor().unwrap().active_workspace_id, Some(0));
+        assert_eq!(state.windows()[&31].workspace_id, 1);
+        assert_eq!(state.windows()[&32].workspace_id, 2);
+        assert_eq!(
+            state.action_request(Action::FocusWorkspace(1)),
+            Some(json!({ "Action": { "FocusWorkspace": { "reference": { "Id": 7 } } } }))
+        );
+
+        // A workspace inserted before the others shifts them but keeps their windows
+        let state = replay(EVENTS_REORDERED, usize::MAX).await;
+
+        assert_eq!(state.workspaces().len(), 4);
+        assert_eq!(state.active_monitor().unwrap().active_workspace_id, Some(1));
+        assert_eq!(state.windows()[&31].workspace_id, 2);
+        assert_eq!(state.windows()[&32].workspace_id, 3);
+        let window = state.active_window(&state.workspaces()[&2]).unwrap();
+        assert_eq!(window.class, "kitty");
+        assert_eq!(
+            state.action_request(Action::MoveWindowToWorkspace(0)),
+            Some(json!({
+                "Action": {
+                    "MoveWindowToWorkspace": {
+                        "window_id": null,
+                        "reference": { "Id": 15 },
+                        "focus": false,
+                    }
+                }
+            }))
+        );
+        assert_eq!(state.action_request(Action::FocusWorkspace(4)), None);
+    }
+}
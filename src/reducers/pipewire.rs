@@ -0,0 +1,270 @@
+use std::{collections::BTreeMap, process::Stdio, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use relm4::{Reducer, Reducible};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+    process::Command,
+    task,
+    time::{sleep, Instant},
+};
+use tracing::{debug, error};
+
+pub static REDUCER: Reducer<PipeWireReducer> = Reducer::new();
+
+const NODE_TYPE: &str = "PipeWire:Interface:Node";
+
+const MIN_RESTART_DELAY: Duration = Duration::from_millis(500);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    Audio,
+    Video,
+}
+
+/// A stream through which an application is capturing audio or video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureStream {
+    pub kind: CaptureKind,
+    pub application: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PipeWireReducer {
+    /// Running capture streams by node id. Video streams include screen shares made through the
+    /// desktop portal.
+    pub capture_streams: BTreeMap<u32, CaptureStream>,
+}
+
+impl PipeWireReducer {
+    pub fn capturing(&self, kind: CaptureKind) -> impl Iterator<Item = &CaptureStream> {
+        self.capture_streams
+            .values()
+            .filter(move |stream| stream.kind == kind)
+    }
+
+    fn apply(&mut self, objects: Vec<RawPipeWireObject>) {
+        for object in objects {
+            let stream = object.info.and_then(|info| {
+                if object.kind != NODE_TYPE || info.state.as_deref() != Some("running") {
+                    return None;
+                }
+                // Level meters, e.g. pavucontrol's, capture sinks' monitors rather than a device
+                let is_true = |key| info.props.get(key).is_some_and(prop_is_true);
+                let target = info.props.get("target.object").and_then(|t| t.as_str());
+                if is_true("stream.monitor") || target.is_some_and(|t| t.ends_with(".monitor")) {
+                    return None;
+                }
+                let kind = match info.props.get("media.class")?.as_str()? {
+                    "Stream/Input/Audio" => CaptureKind::Audio,
+                    "Stream/Input/Video" => CaptureKind::Video,
+                    _ => return None,
+                };
+                let application = ["application.name", "node.name"]
+                    .iter()
+                    .find_map(|key| info.props.get(*key)?.as_str())
+                    .unwrap_or("unknown")
+                    .to_owned();
+                Some(CaptureStream { kind, application })
+            });
+
+            // Removed objects have no info
+            match stream {
+                Some(stream) => self.capture_streams.insert(object.id, stream),
+                None => self.capture_streams.remove(&object.id),
+            };
+        }
+    }
+}
+
+/// Boolean properties may be strings.
+fn prop_is_true(value: &serde_json::Value) -> bool {
+    value
+        .as_bool()
+        .unwrap_or_else(|| value.as_str() == Some("true"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawPipeWireObject {
+    id: u32,
+    #[serde(rename = "type", default)]
+    kind: String,
+    info: Option<RawPipeWireInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPipeWireInfo {
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    props: BTreeMap<String, serde_json::Value>,
+}
+
+pub enum PipeWireInput {
+    Update(Vec<RawPipeWireObject>),
+    /// `pw-dump` exited, it lists every object again when it is restarted.
+    Disconnected,
+}
+
+impl Reducible for PipeWireReducer {
+    type Input = PipeWireInput;
+
+    fn init() -> Self {
+        task::spawn(supervise());
+        Self::default()
+    }
+
+    fn reduce(&mut self, input: Self::Input) -> bool {
+        match input {
+            PipeWireInput::Update(objects) => self.apply(objects),
+            PipeWireInput::Disconnected => self.capture_streams.clear(),
+        }
+        true
+    }
+}
+
+/// Keeps `pw-dump` running, restarting it with exponential backoff when it exits.
+async fn supervise() {
+    let mut delay = MIN_RESTART_DELAY;
+    loop {
+        let started = Instant::now();
+        if let Err(err) = connect().await {
+            error!("watching pipewire failed: {err}");
+        }
+        REDUCER.emit(PipeWireInput::Disconnected);
+
+        // Only back off when pw-dump keeps failing right away, e.g. while PipeWire isn't running
+        if started.elapsed() > MAX_RESTART_DELAY {
+            delay = MIN_RESTART_DELAY;
+        }
+        debug!(?delay, "restarting pw-dump");
+        sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESTART_DELAY);
+    }
+}
+
+/// Watches PipeWire using `pw-dump`, so that libpipewire isn't needed to build.
+async fn connect() -> Result<()> {
+    debug!("watching pipewire");
+
+    let mut child = Command::new("pw-dump")
+        .arg("--monitor")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to run pw-dump")?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to read pw-dump output")?;
+
+    watch(BufReader::new(stdout), |objects| {
+        REDUCER.emit(PipeWireInput::Update(objects));
+    })
+    .await?;
+    bail!("pw-dump exited: {}", child.wait().await?)
+}
+
+/// Reads the output of `pw-dump --monitor`, which prints an array of every object followed by an
+/// array of the changed objects for each update. Arrays end with a line containing only `]`.
+async fn watch(
+    reader: impl AsyncBufRead + Unpin,
+    on_update: impl Fn(Vec<RawPipeWireObject>),
+) -> Result<()> {
+    let mut lines = reader.lines();
+    let mut buffer = String::new();
+
+    while let Some(line) = lines.next_line().await? {
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if line == "]" {
+            let objects = serde_json::from_str(&buffer).context("malformed pw-dump output")?;
+            on_update(objects);
+            buffer.clear();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const DUMP: &str = r#"[
+  {
+    "id": 30,
+    "type": "PipeWire:Interface:Node",
+    "info": {
+      "state": "running",
+      "props": { "media.class": "Stream/Input/Audio", "application.name": "Firefox" }
+    }
+  },
+  {
+    "id": 31,
+    "type": "PipeWire:Interface:Node",
+    "info": {
+      "state": "running",
+      "props": { "media.class": "Stream/Output/Audio", "application.name": "mpv" }
+    }
+  },
+  {
+    "id": 32,
+    "type": "PipeWire:Interface:Node",
+    "info": {
+      "state": "running",
+      "props": { "media.class": "Stream/Input/Video", "node.name": "obs" }
+    }
+  },
+  {
+    "id": 33,
+    "type": "PipeWire:Interface:Node",
+    "info": {
+      "state": "running",
+      "props": {
+        "media.class": "Stream/Input/Audio",
+        "application.name": "PulseAudio Volume Control",
+        "stream.monitor": "true"
+      }
+    }
+  },
+  {
+    "id": 34,
+    "type": "PipeWire:Interface:Node",
+    "info": {
+      "state": "running",
+      "props": {
+        "media.class": "Stream/Input/Audio",
+        "application.name": "easyeffects",
+        "target.object": "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"
+      }
+    }
+  }
+]
+[
+  {
+    "id": 30,
+    "info": null
+  }
+]
+"#;
+
+    #[tokio::test]
+    async fn tracks_capture_streams() {
+        let state = Mutex::new(PipeWireReducer::default());
+        watch(DUMP.as_bytes(), |objects| {
+            state.lock().unwrap().apply(objects)
+        })
+        .await
+        .unwrap();
+        let state = state.into_inner().unwrap();
+
+        assert_eq!(state.capturing(CaptureKind::Audio).count(), 0);
+        let video: Vec<_> = state.capturing(CaptureKind::Video).collect();
+        assert_eq!(video.len(), 1);
+        assert_eq!(video[0].application, "obs");
+    }
+}
@@ -1,21 +1,25 @@
-use std::{cell::RefCell, ops::Deref, rc::Rc};
+use std::{cell::RefCell, ops::Deref, rc::Rc, sync::Mutex};
 
 use anyhow::{bail, ensure, Context as AnyhowContext, Result};
 use libpulse_binding as pulse;
 use pulse::{
     callbacks::ListResult,
-    context::{subscribe::InterestMaskSet, Context, FlagSet, State},
+    context::{
+        subscribe::{Facility, InterestMaskSet},
+        Context, FlagSet, State,
+    },
     mainloop::threaded::Mainloop,
     proplist::Proplist,
 };
 use relm4::{Reducer, Reducible};
-use tokio::{sync::OnceCell, task};
+use tokio::task;
 use tracing::{debug, error, trace, warn};
 
 use crate::APPLICATION_NAME;
 
 pub static REDUCER: Reducer<PulseAudioReducer> = Reducer::new();
-static DEFAULT_SINK_NAME: OnceCell<String> = OnceCell::const_new();
+/// Name of the default sink, if it is known yet. Changes when the user switches output devices.
+static DEFAULT_SINK_NAME: Mutex<Option<String>> = Mutex::new(None);
 
 #[derive(Debug, Clone)]
 pub struct PulseAudioReducer {
@@ -28,7 +32,7 @@ pub struct PulseAudioReducer {
 }
 
 pub enum PulseAudioInput {
-    Update(u32, bool),
+    Update { volume: u32, muted: bool },
 }
 
 impl Reducible for PulseAudioReducer {
@@ -49,10 +53,10 @@ impl Reducible for PulseAudioReducer {
 
     fn reduce(&mut self, input: Self::Input) -> bool {
         match input {
-            PulseAudioInput::Update(volume, muted) => {
+            PulseAudioInput::Update { volume, muted } => {
                 self.volume = f32::round((volume as f32) / 65535.0 * 100.0);
                 self.muted = muted;
-                trace!("volume: {}%, muted: {}", self.volume, muted);
+                trace!("volume: {}%, muted: {muted}", self.volume);
             }
         }
         true
@@ -106,6 +110,9 @@ async fn connect() -> Result<()> {
         .borrow_mut()
         .connect(None, FlagSet::NOFLAGS, None)
         .context("failed to connect context")?;
+    // Callbacks run on the mainloop thread while it holds the lock. Everything here holds the lock
+    // too, so that the context is never used by both threads at once.
+    mainloop.borrow_mut().lock();
     mainloop
         .borrow_mut()
         .start()
@@ -113,7 +120,8 @@ async fn connect() -> Result<()> {
 
     // Wait for context to be ready
     loop {
-        match context.borrow().get_state() {
+        let state = context.borrow().get_state();
+        match state {
             State::Ready => break,
             State::Failed | State::Terminated => {
                 mainloop.borrow_mut().unlock();
@@ -124,64 +132,75 @@ async fn connect() -> Result<()> {
         };
     }
 
-    // Subscribe to sink events
+    // Subscribe to sink events, and server events for changes to the default sink
     {
         let context_ref = Rc::clone(&context);
         context
             .borrow_mut()
-            .set_subscribe_callback(Some(Box::new(move |_, _, _| {
-                let Some(default_sink_name) = DEFAULT_SINK_NAME.get() else {
-                    warn!("got sink event before default sink is known");
-                    return;
-                };
-                unsafe {
-                    (*context_ref.as_ptr()).introspect().get_sink_info_by_name(
-                        &default_sink_name,
-                        move |info| {
-                            let ListResult::Item(info) = info else { return };
-                            let volume = info.volume.get()[0].0;
-                            let muted = info.mute;
-                            REDUCER.emit(PulseAudioInput::Update(volume, muted));
-                        },
-                    )
-                };
+            .set_subscribe_callback(Some(Box::new(move |facility, _, _| match facility {
+                Some(Facility::Server) => update_default_sink(&context_ref),
+                Some(Facility::Sink) => {
+                    let default_sink_name = DEFAULT_SINK_NAME.lock().unwrap().clone();
+                    let Some(default_sink_name) = default_sink_name else {
+                        warn!("got sink event before default sink is known");
+                        return;
+                    };
+                    update_sink(&context_ref, &default_sink_name);
+                }
+                _ => {}
             })));
 
-        context
-            .borrow_mut()
-            .subscribe(InterestMaskSet::SINK, |success| {
+        context.borrow_mut().subscribe(
+            InterestMaskSet::SINK | InterestMaskSet::SERVER,
+            |success| {
                 if success {
-                    debug!("successfully subscribed to sink events");
+                    debug!("successfully subscribed to sink and server events");
                 } else {
-                    debug!("failed to subscribe to sink events");
+                    debug!("failed to subscribe to sink and server events");
                 }
-            });
+            },
+        );
     }
 
-    // Get default sink
-    {
-        let introspect = context.borrow().introspect();
-        introspect.get_server_info(move |info| {
-            let Some(default_sink_name) = &info.default_sink_name else {
-                error!("failed to find default sink");
-                return;
-            };
-
-            DEFAULT_SINK_NAME
-                .set(default_sink_name.to_string())
-                .expect("failed to store default sink name");
+    // Get default sink and initial state
+    update_default_sink(&context);
+    mainloop.borrow_mut().unlock();
+
+    Ok(())
+}
+
+/// Looks up the default sink, then emits its state. The mainloop must be locked.
+fn update_default_sink(context: &Rc<RefCell<Context>>) {
+    // Callbacks use the context through its pointer rather than borrowing the RefCell
+    let introspect = unsafe { (*context.as_ptr()).introspect() };
+    let context = Rc::clone(context);
+    introspect.get_server_info(move |info| {
+        let Some(default_sink_name) = &info.default_sink_name else {
+            error!("failed to find default sink");
+            return;
+        };
+        let default_sink_name = default_sink_name.to_string();
+
+        let previous = DEFAULT_SINK_NAME
+            .lock()
+            .unwrap()
+            .replace(default_sink_name.clone());
+        if previous.as_ref() != Some(&default_sink_name) {
             debug!("got default sink name: {}", default_sink_name);
+        }
 
-            // Get initial state
-            let introspect = context.borrow().introspect();
-            introspect.get_sink_info_by_name(default_sink_name, move |info| {
-                let ListResult::Item(info) = info else { return };
-                let volume = info.volume.get()[0].0;
-                let muted = info.mute;
-                REDUCER.emit(PulseAudioInput::Update(volume, muted));
-            });
-        });
-    }
+        update_sink(&context, &default_sink_name);
+    });
+}
 
-    Ok(())
+/// Emits the volume and mute state of a sink. The mainloop must be locked.
+fn update_sink(context: &Rc<RefCell<Context>>, sink_name: &str) {
+    let introspect = unsafe { (*context.as_ptr()).introspect() };
+    introspect.get_sink_info_by_name(sink_name, move |info| {
+        let ListResult::Item(info) = info else { return };
+        REDUCER.emit(PulseAudioInput::Update {
+            volume: info.volume.get()[0].0,
+            muted: info.mute,
+        });
+    });
 }
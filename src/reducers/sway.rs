@@ -0,0 +1,769 @@
+use std::{
+    collections::BTreeMap,
+    env, iter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use relm4::{Reducer, Reducible};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    task,
+    time::sleep,
+};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::data::wayland_compositor::{
+    Action, Monitor, MonitorConnector, SpecialWorkspace, WaylandCompositor, Window, WindowId,
+    Workspace, WorkspaceId,
+};
+
+pub static REDUCER: Reducer<SwayReducer> = Reducer::new();
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// Sway implements the i3 IPC protocol: https://i3wm.org/docs/ipc.html
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LENGTH: usize = MAGIC.len() + 8;
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+/// Set in the message type of events.
+const EVENT: u32 = 1 << 31;
+const EVENT_WORKSPACE: u32 = EVENT;
+const EVENT_OUTPUT: u32 = EVENT | 1;
+const EVENT_WINDOW: u32 = EVENT | 3;
+const EVENT_SHUTDOWN: u32 = EVENT | 6;
+
+/// Output which holds the scratchpad.
+const INTERNAL_OUTPUT: &str = "__i3";
+const SCRATCHPAD_WORKSPACE: &str = "__i3_scratch";
+/// Name of the scratchpad as a special workspace.
+const SCRATCHPAD: &str = "scratchpad";
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSwayNode {
+    id: usize,
+    #[serde(rename = "type")]
+    node_type: String,
+    name: Option<String>,
+    /// Workspace number, -1 for workspaces without a number.
+    num: Option<i32>,
+    /// Set for Wayland windows in Sway.
+    app_id: Option<String>,
+    /// Set for X11 windows.
+    window_properties: Option<RawSwayWindowProperties>,
+    #[serde(default)]
+    urgent: bool,
+    /// IDs of the children, most recently focused first.
+    #[serde(default)]
+    focus: Vec<usize>,
+    #[serde(default)]
+    nodes: Vec<RawSwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<RawSwayNode>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSwayWindowProperties {
+    class: Option<String>,
+}
+
+/// Workspace as reported by GET_WORKSPACES.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawSwayWorkspace {
+    name: String,
+    num: i32,
+    output: String,
+    visible: bool,
+    focused: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSwayWorkspaceEvent {
+    change: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawSwayWindowEvent {
+    change: String,
+    container: RawSwayNode,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSwaySubscribeReply {
+    success: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RawSwayCommandReply {
+    success: bool,
+    error: Option<String>,
+}
+
+impl RawSwayNode {
+    fn children(&self) -> impl Iterator<Item = &RawSwayNode> {
+        self.nodes.iter().chain(&self.floating_nodes)
+    }
+
+    fn is_window(&self) -> bool {
+        self.app_id.is_some() || self.window_properties.is_some()
+    }
+
+    fn workspace_id(&self) -> Option<WorkspaceId> {
+        workspace_id(self.num?)
+    }
+
+    fn class(&self) -> String {
+        self.app_id
+            .clone()
+            .or_else(|| {
+                self.window_properties
+                    .as_ref()
+                    .and_then(|p| p.class.clone())
+            })
+            .unwrap_or_default()
+    }
+
+    fn focused_child(&self) -> Option<&RawSwayNode> {
+        let id = self.focus.first()?;
+        self.children().find(|child| child.id == *id)
+    }
+
+    /// Follows the most recently focused children down from this node.
+    fn focused_descendants(&self) -> impl Iterator<Item = &RawSwayNode> {
+        iter::successors(self.focused_child(), |node| node.focused_child())
+    }
+
+    /// Finds workspaces under an output. i3 places them in a "content" container, Sway does not.
+    fn workspaces(&self) -> Vec<&RawSwayNode> {
+        self.children()
+            .flat_map(|child| match child.node_type.as_str() {
+                "workspace" => vec![child],
+                _ => child.workspaces(),
+            })
+            .collect()
+    }
+
+    fn windows(&self) -> Vec<&RawSwayNode> {
+        self.children()
+            .flat_map(|child| {
+                if child.is_window() {
+                    vec![child]
+                } else {
+                    child.windows()
+                }
+            })
+            .collect()
+    }
+
+    fn postprocess(root: RawSwayNode) -> SwayReducer {
+        let mut state = SwayReducer::default();
+
+        let outputs = root
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == "output" && n.name.as_deref() != Some(INTERNAL_OUTPUT));
+        for output in outputs {
+            let connector = output.name.clone().unwrap_or_default();
+
+            for raw_workspace in output.workspaces() {
+                // Filter workspaces without a number, they cannot be shown as circles yet
+                let Some(id) = raw_workspace.workspace_id() else {
+                    trace!(
+                        { name = raw_workspace.name },
+                        "ignoring unnumbered sway workspace"
+                    );
+                    continue;
+                };
+
+                let active_window_id = raw_workspace
+                    .focused_descendants()
+                    .find(|n| n.is_window())
+                    .map(|n| n.id);
+                state.workspaces.insert(
+                    id,
+                    Workspace {
+                        id,
+                        name: raw_workspace.name.clone().unwrap_or_default(),
+                        monitor_connector: connector.clone(),
+                        active_window_id,
+                    },
+                );
+
+                for raw_window in raw_workspace.windows() {
+                    state.windows.insert(
+                        raw_window.id,
+                        Window {
+                            id: raw_window.id,
+                            class: raw_window.class(),
+                            title: raw_window.name.clone().unwrap_or_default(),
+                            workspace_id: id,
+                            urgent: raw_window.urgent,
+                        },
+                    );
+                }
+            }
+
+            let active_workspace_id = output
+                .focused_descendants()
+                .find(|n| n.node_type == "workspace")
+                .and_then(|n| n.workspace_id());
+            state.monitors.insert(
+                connector.clone(),
+                Monitor {
+                    connector,
+                    active: root.focus.first() == Some(&output.id),
+                    active_workspace_id,
+                    active_special_workspace: None,
+                },
+            );
+        }
+
+        let scratchpad = root
+            .nodes
+            .iter()
+            .filter(|n| n.name.as_deref() == Some(INTERNAL_OUTPUT))
+            .flat_map(|n| n.workspaces())
+            .find(|n| n.name.as_deref() == Some(SCRATCHPAD_WORKSPACE));
+        if let Some(scratchpad) = scratchpad {
+            let windows = scratchpad.windows();
+            if !windows.is_empty() {
+                state.special_workspaces.insert(
+                    SCRATCHPAD.to_owned(),
+                    SpecialWorkspace {
+                        name: SCRATCHPAD.to_owned(),
+                        n_windows: windows.len(),
+                        urgent: windows.iter().any(|w| w.urgent),
+                    },
+                );
+            }
+        }
+
+        state
+    }
+}
+
+/// Converts a 1-based workspace number to a 0-based id.
+fn workspace_id(num: i32) -> Option<WorkspaceId> {
+    (num > 0).then(|| num as usize - 1)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SwayReducer {
+    monitors: BTreeMap<MonitorConnector, Monitor>,
+    workspaces: BTreeMap<WorkspaceId, Workspace>,
+    windows: BTreeMap<WindowId, Window>,
+    special_workspaces: BTreeMap<String, SpecialWorkspace>,
+}
+
+impl SwayReducer {
+    /// Updates workspaces and the workspaces shown on each monitor. Windows stay where they are, as
+    /// workspace changes which move them are handled by refreshing the whole tree.
+    fn update_workspaces(&mut self, raw: Vec<RawSwayWorkspace>) {
+        let mut workspaces = BTreeMap::new();
+        for raw_workspace in &raw {
+            let Some(id) = workspace_id(raw_workspace.num) else {
+                continue;
+            };
+            // GET_WORKSPACES doesn't tell which window is focused
+            let active_window_id = self.workspaces.get(&id).and_then(|w| w.active_window_id);
+            workspaces.insert(
+                id,
+                Workspace {
+                    id,
+                    name: raw_workspace.name.clone(),
+                    monitor_connector: raw_workspace.output.clone(),
+                    active_window_id,
+                },
+            );
+        }
+        self.workspaces = workspaces;
+
+        for monitor in self.monitors.values_mut() {
+            let mut on_monitor = raw.iter().filter(|w| w.output == monitor.connector);
+            monitor.active = on_monitor.clone().any(|w| w.focused);
+            monitor.active_workspace_id = on_monitor
+                .find(|w| w.visible)
+                .and_then(|w| workspace_id(w.num));
+        }
+    }
+
+    /// Applies a window event. Returns false if the tree must be refreshed instead, e.g. because the
+    /// event doesn't say which workspace the window is on.
+    fn apply_window_event(&mut self, event: RawSwayWindowEvent) -> bool {
+        let container = event.container;
+        match event.change.as_str() {
+            "title" => {
+                // Windows on unnumbered workspaces aren't tracked
+                if let Some(window) = self.windows.get_mut(&container.id) {
+                    window.title = container.name.clone().unwrap_or_default();
+                    window.class = container.class();
+                }
+                true
+            }
+            "urgent" => {
+                // Windows in the scratchpad aren't tracked, but its urgency is
+                let Some(window) = self.windows.get_mut(&container.id) else {
+                    return false;
+                };
+                window.urgent = container.urgent;
+                true
+            }
+            "focus" => {
+                let workspace_id = self.windows.get(&container.id).map(|w| w.workspace_id);
+                if let Some(workspace) = workspace_id.and_then(|id| self.workspaces.get_mut(&id)) {
+                    workspace.active_window_id = Some(container.id);
+                }
+                true
+            }
+            "close" => {
+                // Windows closed in the scratchpad change its window count
+                let Some(window) = self.windows.remove(&container.id) else {
+                    return false;
+                };
+                // Sway sends a focus event for the next window
+                if let Some(workspace) = self.workspaces.get_mut(&window.workspace_id) {
+                    if workspace.active_window_id == Some(window.id) {
+                        workspace.active_window_id = None;
+                    }
+                }
+                true
+            }
+            "new" | "move" | "floating" => false,
+            _ => true,
+        }
+    }
+}
+
+impl WaylandCompositor for SwayReducer {
+    fn monitors(&self) -> &BTreeMap<MonitorConnector, Monitor> {
+        &self.monitors
+    }
+
+    fn workspaces(&self) -> &BTreeMap<WorkspaceId, Workspace> {
+        &self.workspaces
+    }
+
+    fn windows(&self) -> &BTreeMap<WindowId, Window> {
+        &self.windows
+    }
+
+    fn special_workspaces(&self) -> &BTreeMap<String, SpecialWorkspace> {
+        &self.special_workspaces
+    }
+
+    fn dispatch(&self, action: Action) {
+        let command = match action {
+            Action::FocusWorkspace(id) => format!("workspace number {}", id + 1),
+            Action::MoveWindowToWorkspace(id) => {
+                format!("move container to workspace number {}", id + 1)
+            }
+            Action::FocusWindow(id) => format!("[con_id={id}] focus"),
+            Action::CloseWindow(id) => format!("[con_id={id}] kill"),
+        };
+        task::spawn(async move {
+            if let Err(err) = run_command(&command).await {
+                error!("sway command failed: {err}");
+            }
+        });
+    }
+}
+
+pub enum SwayInput {
+    RequestRefresh,
+    Refresh(SwayReducer),
+    Workspaces(Vec<RawSwayWorkspace>),
+    Window(RawSwayWindowEvent),
+    /// The IPC connection closed or could not be established.
+    Disconnected,
+}
+
+impl Reducible for SwayReducer {
+    type Input = SwayInput;
+
+    fn init() -> Self {
+        task::spawn(supervise());
+        Self::default()
+    }
+
+    fn reduce(&mut self, input: Self::Input) -> bool {
+        match input {
+            SwayInput::RequestRefresh => {
+                task::spawn(async move {
+                    if let Err(err) = refresh().await {
+                        error!("getting sway tree failed: {err}");
+                    }
+                });
+            }
+            SwayInput::Refresh(state) => {
+                *self = state;
+            }
+            SwayInput::Workspaces(workspaces) => self.update_workspaces(workspaces),
+            SwayInput::Window(event) => {
+                if !self.apply_window_event(event) {
+                    self.reduce(SwayInput::RequestRefresh);
+                }
+            }
+            SwayInput::Disconnected => {
+                *self = Self::default();
+            }
+        }
+        true
+    }
+}
+
+fn socket_path() -> Result<PathBuf> {
+    env::var_os("SWAYSOCK")
+        .or_else(|| env::var_os("I3SOCK"))
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("neither $SWAYSOCK nor $I3SOCK is set"))
+}
+
+struct Connection {
+    stream: UnixStream,
+}
+
+impl Connection {
+    async fn connect(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path).await?;
+        Ok(Self { stream })
+    }
+
+    async fn send(&mut self, message_type: u32, payload: &[u8]) -> Result<()> {
+        let mut message = Vec::with_capacity(HEADER_LENGTH + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload);
+        self.stream.write_all(&message).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<(u32, Vec<u8>)> {
+        let mut header = [0; HEADER_LENGTH];
+        self.stream.read_exact(&mut header).await?;
+        if !header.starts_with(MAGIC) {
+            bail!("malformed sway ipc message header: {header:?}");
+        }
+        let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+        let message_type = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+        let mut payload = vec![0; length as usize];
+        self.stream.read_exact(&mut payload).await?;
+        Ok((message_type, payload))
+    }
+
+    /// Sends a message and waits for the reply, skipping any events received in the meantime.
+    async fn request(&mut self, message_type: u32, payload: &[u8]) -> Result<Vec<u8>> {
+        self.send(message_type, payload).await?;
+        loop {
+            let (reply_type, reply) = self.receive().await?;
+            if reply_type == message_type {
+                return Ok(reply);
+            }
+        }
+    }
+
+    async fn get_tree(&mut self) -> Result<SwayReducer> {
+        let tree = self.request(GET_TREE, b"").await?;
+        let tree: RawSwayNode = serde_json::from_slice(&tree)?;
+        Ok(RawSwayNode::postprocess(tree))
+    }
+
+    async fn get_workspaces(&mut self) -> Result<Vec<RawSwayWorkspace>> {
+        let workspaces = self.request(GET_WORKSPACES, b"").await?;
+        Ok(serde_json::from_slice(&workspaces)?)
+    }
+}
+
+async fn refresh() -> Result<()> {
+    let mut connection = Connection::connect(&socket_path()?).await?;
+    REDUCER.emit(SwayInput::Refresh(connection.get_tree().await?));
+    Ok(())
+}
+
+async fn run_command(command: &str) -> Result<()> {
+    let mut connection = Connection::connect(&socket_path()?).await?;
+    let replies = connection.request(RUN_COMMAND, command.as_bytes()).await?;
+    let replies: Vec<RawSwayCommandReply> = serde_json::from_slice(&replies)?;
+    for reply in replies {
+        if !reply.success {
+            bail!("\"{command}\": {}", reply.error.unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+async fn supervise() {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(err) => {
+            error!("sway ipc connection failed: {err}");
+            return;
+        }
+    };
+
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match subscribe(&path).await {
+            Ok((requests, events)) => {
+                info!("connected to sway ipc");
+                delay = MIN_RECONNECT_DELAY;
+
+                match watch(requests, events, |input| REDUCER.emit(input)).await {
+                    Ok(()) => warn!("sway shut down"),
+                    Err(err) => error!("sway ipc connection failed: {err}"),
+                }
+            }
+            Err(err) => error!("sway ipc connection failed: {err}"),
+        }
+
+        REDUCER.emit(SwayInput::Disconnected);
+        debug!(?delay, "reconnecting to sway ipc");
+        sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Connects to the compositor at `path`. Returns a connection for requests and one subscribed to
+/// events.
+async fn subscribe(path: &Path) -> Result<(Connection, Connection)> {
+    let requests = Connection::connect(path).await?;
+    let mut events = Connection::connect(path).await?;
+
+    let reply = events
+        .request(
+            SUBSCRIBE,
+            br#"["workspace", "window", "output", "shutdown"]"#,
+        )
+        .await?;
+    let reply: RawSwaySubscribeReply = serde_json::from_slice(&reply)?;
+    if !reply.success {
+        bail!("failed to subscribe to sway events");
+    }
+
+    Ok((requests, events))
+}
+
+/// Calls `on_input` with the state of the compositor, then with the changes reported by each event.
+/// Returns when the compositor shuts down.
+async fn watch(
+    mut requests: Connection,
+    mut events: Connection,
+    on_input: impl Fn(SwayInput),
+) -> Result<()> {
+    on_input(SwayInput::Refresh(requests.get_tree().await?));
+    loop {
+        let (event_type, payload) = events.receive().await?;
+        trace!(
+            { event_type = event_type & !EVENT, payload = %String::from_utf8_lossy(&payload) },
+            "sway ipc event"
+        );
+        match event_type {
+            EVENT_WORKSPACE => {
+                let event: RawSwayWorkspaceEvent = serde_json::from_slice(&payload)?;
+                // Renaming may change the number, and so the ids windows refer to
+                if matches!(event.change.as_str(), "rename" | "reload") {
+                    on_input(SwayInput::Refresh(requests.get_tree().await?));
+                } else {
+                    on_input(SwayInput::Workspaces(requests.get_workspaces().await?));
+                }
+            }
+            EVENT_WINDOW => on_input(SwayInput::Window(serde_json::from_slice(&payload)?)),
+            EVENT_OUTPUT => on_input(SwayInput::Refresh(requests.get_tree().await?)),
+            EVENT_SHUTDOWN => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    const TREE_INITIAL: &str = include_str!("../../tests/fixtures/sway/tree_initial.json");
+    const TREE_MOVED: &str = include_str!("../../tests/fixtures/sway/tree_moved.json");
+    const WORKSPACES_FOCUSED: &str =
+        include_str!("../../tests/fixtures/sway/workspaces_focused.json");
+    const WORKSPACE_FOCUSED: &str = include_str!("../../tests/fixtures/sway/event_workspace.json");
+    const WINDOW_TITLE: &str = include_str!("../../tests/fixtures/sway/event_window_title.json");
+
+    /// Serves recorded replies to GET_TREE and GET_WORKSPACES in order, and sends the recorded
+    /// events to subscribers followed by a shutdown event.
+    fn fake_sway(path: &Path, replies: Vec<(u32, &'static str)>, events: Vec<(u32, &'static str)>) {
+        let listener = UnixListener::bind(path).unwrap();
+        let replies = Arc::new(Mutex::new(VecDeque::from(replies)));
+
+        task::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut connection = Connection { stream };
+                let replies = replies.clone();
+                let events = events.clone();
+
+                task::spawn(async move {
+                    while let Ok((message_type, _)) = connection.receive().await {
+                        match message_type {
+                            GET_TREE | GET_WORKSPACES => {
+                                let (reply_type, reply) =
+                                    replies.lock().unwrap().pop_front().unwrap();
+                                assert_eq!(message_type, reply_type);
+                                connection.send(reply_type, reply.as_bytes()).await.unwrap();
+                            }
+                            SUBSCRIBE => {
+                                let reply = br#"{"success": true}"#;
+                                connection.send(SUBSCRIBE, reply).await.unwrap();
+                                for (event_type, event) in &events {
+                                    connection
+                                        .send(*event_type, event.as_bytes())
+                                        .await
+                                        .unwrap();
+                                }
+                                connection.send(EVENT_SHUTDOWN, b"{}").await.unwrap();
+                            }
+                            _ => panic!("unexpected message type {message_type}"),
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn parses_tree() {
+        let tree = serde_json::from_str(TREE_INITIAL).unwrap();
+        let state = RawSwayNode::postprocess(tree);
+
+        let monitors: Vec<_> = state.monitors().values().cloned().collect();
+        assert_eq!(
+            monitors,
+            [
+                Monitor {
+                    connector: "DP-1".into(),
+                    active: true,
+                    active_workspace_id: Some(0),
+                    active_special_workspace: None,
+                },
+                Monitor {
+                    connector: "HDMI-A-1".into(),
+                    active: false,
+                    active_workspace_id: Some(4),
+                    active_special_workspace: None,
+                },
+            ]
+        );
+
+        // The scratchpad and unnumbered workspaces are ignored
+        assert_eq!(state.workspaces().keys().collect::<Vec<_>>(), [&0, &1, &4]);
+        assert_eq!(state.special_workspaces()[SCRATCHPAD].n_windows, 1);
+        assert_eq!(
+            state.windows().keys().collect::<Vec<_>>(),
+            [&10, &11, &12, &20]
+        );
+
+        let workspace = &state.workspaces()[&0];
+        assert_eq!(workspace.monitor_connector, "DP-1");
+        assert_eq!(state.active_window(workspace).unwrap().id, 11);
+        assert_eq!(state.windows_in_workspace(workspace).len(), 3);
+        assert!(state.workspace_is_empty(&state.workspaces()[&1]));
+
+        let window = &state.windows()[&12];
+        assert_eq!(window.class, "steam");
+        assert_eq!(window.title, "Steam");
+        let window = &state.windows()[&20];
+        assert_eq!(window.class, "firefox");
+        assert_eq!(window.workspace_id, 4);
+    }
+
+    fn window_event(change: &str, id: WindowId) -> RawSwayWindowEvent {
+        let event =
+            format!(r#"{{"change": "{change}", "container": {{"id": {id}, "type": "con"}}}}"#);
+        serde_json::from_str(&event).unwrap()
+    }
+
+    #[test]
+    fn applies_window_events() {
+        let mut state = RawSwayNode::postprocess(serde_json::from_str(TREE_INITIAL).unwrap());
+
+        assert!(state.apply_window_event(window_event("focus", 10)));
+        assert_eq!(state.workspaces()[&0].active_window_id, Some(10));
+        assert!(state.apply_window_event(window_event("close", 10)));
+        assert!(!state.windows().contains_key(&10));
+        assert_eq!(state.workspaces()[&0].active_window_id, None);
+
+        // Windows on unnumbered workspaces are not tracked
+        assert!(state.apply_window_event(window_event("title", 13)));
+        // The scratchpad window count changes
+        assert!(!state.apply_window_event(window_event("close", 30)));
+        // The window's workspace is unknown
+        assert!(!state.apply_window_event(window_event("new", 40)));
+    }
+
+    #[tokio::test]
+    async fn replays_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sway-ipc.sock");
+        fake_sway(
+            &path,
+            vec![
+                (GET_TREE, TREE_INITIAL),
+                (GET_WORKSPACES, WORKSPACES_FOCUSED),
+                (GET_TREE, TREE_MOVED),
+            ],
+            vec![
+                (EVENT_WORKSPACE, WORKSPACE_FOCUSED),
+                (EVENT_WINDOW, WINDOW_TITLE),
+                (EVENT_OUTPUT, r#"{"change": "unspecified"}"#),
+            ],
+        );
+
+        let inputs = Mutex::new(Vec::new());
+        let (requests, events) = subscribe(&path).await.unwrap();
+        watch(requests, events, |input| inputs.lock().unwrap().push(input))
+            .await
+            .unwrap();
+
+        let mut inputs = inputs.into_inner().unwrap().into_iter();
+        let Some(SwayInput::Refresh(mut state)) = inputs.next() else {
+            panic!("expected the initial tree");
+        };
+        assert_eq!(state.active_monitor().unwrap().connector, "DP-1");
+
+        let Some(SwayInput::Workspaces(workspaces)) = inputs.next() else {
+            panic!("expected workspaces");
+        };
+        state.update_workspaces(workspaces);
+        assert_eq!(state.active_monitor().unwrap().connector, "HDMI-A-1");
+        assert_eq!(state.monitors()["HDMI-A-1"].active_workspace_id, Some(4));
+        assert_eq!(state.monitors()["DP-1"].active_workspace_id, Some(0));
+        assert_eq!(state.workspaces()[&0].active_window_id, Some(11));
+
+        let Some(SwayInput::Window(event)) = inputs.next() else {
+            panic!("expected a window event");
+        };
+        assert!(state.apply_window_event(event));
+        assert_eq!(
+            state.windows()[&20].title,
+            "Rust Programming Language - Mozilla Firefox"
+        );
+
+        let Some(SwayInput::Refresh(state)) = inputs.next() else {
+            panic!("expected a refreshed tree");
+        };
+        assert_eq!(state.windows()[&11].workspace_id, 4);
+        assert!(inputs.next().is_none());
+    }
+}
@@ -1,10 +1,10 @@
 use gtk::{
     prelude::{DisplayExt, MonitorExt, SurfaceExt},
-    traits::NativeExt,
+    traits::{NativeExt, OrientableExt},
 };
 use relm4::RelmWidgetExt;
 
-use crate::pango_span;
+use crate::{config, pango_span};
 
 pub fn dim_if(text: String, cond: bool) -> String {
     if cond {
@@ -22,14 +22,27 @@ pub fn pad_with_dim_leading_zeros(text: String, max_length: usize) -> String {
 
 pub trait UtilWidgetExt {
     fn monitor_connector(&self) -> String;
+
+    /// Lays out children along the bar, e.g. stacking an icon over its label on vertical bars.
+    /// The monitor is only known once the widget is realized.
+    fn orient_along_bar(&self)
+    where
+        Self: gtk::glib::IsA<gtk::Orientable>;
 }
 
 impl<T: gtk::glib::IsA<gtk::Widget>> UtilWidgetExt for T {
     fn monitor_connector(&self) -> String {
-        let surface = self
+        let window = self
             .toplevel_window()
-            .expect("widget has no toplevel window")
-            .surface();
+            .expect("widget has no toplevel window");
+
+        // Bars are assigned a monitor when they are created, prefer it to avoid depending on the
+        // window having been mapped.
+        if let Some(connector) = gtk4_layer_shell::monitor(&window).and_then(|m| m.connector()) {
+            return connector.to_string();
+        }
+
+        let surface = window.surface();
         let connector = surface
             .display()
             .monitor_at_surface(&surface)
@@ -39,4 +52,12 @@ impl<T: gtk::glib::IsA<gtk::Widget>> UtilWidgetExt for T {
 
         connector.to_string()
     }
+
+    fn orient_along_bar(&self)
+    where
+        Self: gtk::glib::IsA<gtk::Orientable>,
+    {
+        let orientation = config::get().monitor(self).window.edge.orientation();
+        self.set_orientation(orientation);
+    }
 }